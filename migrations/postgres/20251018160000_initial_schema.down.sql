@@ -0,0 +1,4 @@
+DROP TABLE image_renditions;
+DROP TABLE refresh_tokens;
+DROP TABLE images;
+DROP TABLE users;
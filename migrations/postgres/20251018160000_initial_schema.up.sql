@@ -0,0 +1,39 @@
+CREATE TABLE users (
+	id BIGSERIAL PRIMARY KEY NOT NULL,
+	username TEXT UNIQUE NOT NULL,
+	passhash TEXT NOT NULL
+);
+
+CREATE TABLE images (
+	id TEXT PRIMARY KEY NOT NULL,
+	user_id BIGINT NOT NULL,
+	extension TEXT NOT NULL,
+	width BIGINT NOT NULL DEFAULT 0,
+	height BIGINT NOT NULL DEFAULT 0,
+	size_bytes BIGINT NOT NULL DEFAULT 0,
+	created_at TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00',
+	original_filename TEXT,
+	FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX images_created_at ON images (created_at, id);
+CREATE INDEX images_user_id_created_at ON images (user_id, created_at, id);
+
+CREATE TABLE refresh_tokens (
+	token_hash TEXT PRIMARY KEY NOT NULL,
+	user_id BIGINT NOT NULL,
+	created_at TIMESTAMP NOT NULL,
+	expires_at TIMESTAMP NOT NULL,
+	revoked_at TIMESTAMP,
+	FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE TABLE image_renditions (
+	image_id TEXT NOT NULL,
+	width BIGINT NOT NULL,
+	height BIGINT NOT NULL,
+	extension TEXT NOT NULL,
+	size_bytes BIGINT NOT NULL,
+	PRIMARY KEY (image_id, width),
+	FOREIGN KEY (image_id) REFERENCES images (id) ON DELETE CASCADE
+);
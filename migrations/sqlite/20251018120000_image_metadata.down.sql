@@ -0,0 +1,5 @@
+ALTER TABLE images DROP COLUMN original_filename;
+ALTER TABLE images DROP COLUMN created_at;
+ALTER TABLE images DROP COLUMN size_bytes;
+ALTER TABLE images DROP COLUMN height;
+ALTER TABLE images DROP COLUMN width;
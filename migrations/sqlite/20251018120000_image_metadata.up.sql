@@ -0,0 +1,5 @@
+ALTER TABLE images ADD COLUMN width INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE images ADD COLUMN height INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE images ADD COLUMN size_bytes INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE images ADD COLUMN created_at DATETIME NOT NULL DEFAULT '1970-01-01 00:00:00';
+ALTER TABLE images ADD COLUMN original_filename TEXT;
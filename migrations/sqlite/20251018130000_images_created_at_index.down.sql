@@ -0,0 +1,2 @@
+DROP INDEX images_user_id_created_at;
+DROP INDEX images_created_at;
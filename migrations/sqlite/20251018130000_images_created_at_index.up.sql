@@ -0,0 +1,2 @@
+CREATE INDEX images_created_at ON images (created_at, id);
+CREATE INDEX images_user_id_created_at ON images (user_id, created_at, id);
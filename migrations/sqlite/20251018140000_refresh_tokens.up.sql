@@ -0,0 +1,8 @@
+CREATE TABLE refresh_tokens (
+	token_hash TEXT PRIMARY KEY NOT NULL,
+	user_id INTEGER NOT NULL,
+	created_at DATETIME NOT NULL,
+	expires_at DATETIME NOT NULL,
+	revoked_at DATETIME,
+	FOREIGN KEY (user_id) REFERENCES users (id)
+);
@@ -0,0 +1,9 @@
+CREATE TABLE image_renditions (
+	image_id TEXT NOT NULL,
+	width INTEGER NOT NULL,
+	height INTEGER NOT NULL,
+	extension TEXT NOT NULL,
+	size_bytes INTEGER NOT NULL,
+	PRIMARY KEY (image_id, width),
+	FOREIGN KEY (image_id) REFERENCES images (id) ON DELETE CASCADE
+);
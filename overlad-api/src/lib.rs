@@ -1,5 +1,19 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+/// The font used when an overlay does not ask for one by name.
+pub const DEFAULT_FONT: &str = "roboto";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ErrorResponse {
+    pub error: String,
+    /// Problems with individual request fields, keyed by field name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct RegisterRequest {
     pub username: String,
@@ -7,9 +21,22 @@ pub struct RegisterRequest {
     pub confirm_password: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChangePasswordRequest {
+    pub old_password: String,
+    pub new_password: String,
+    pub confirm_password: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TokenClaims {
     pub sub: i64,
+    /// When the token was issued, in seconds since the Unix epoch.
+    pub iat: i64,
+    /// When the token expires, in seconds since the Unix epoch.
+    pub exp: i64,
+    /// A unique id for the token.
+    pub jti: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -21,6 +48,12 @@ pub struct TokenRequest {
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TokenResponse {
     pub token: String,
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RefreshTokenRequest {
+    pub refresh_token: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -34,4 +67,26 @@ pub struct Image {
     pub id: String,
     pub user: User,
     pub extension: String,
+    pub width: u32,
+    pub height: u32,
+    pub size_bytes: u64,
+    pub created_at: DateTime<Utc>,
+    pub original_filename: Option<String>,
+}
+
+/// The order images are listed in, by upload time.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageSort {
+    #[default]
+    Newest,
+    Oldest,
+}
+
+/// A page of images. Pass `next_cursor` back as `cursor` to get the next page,
+/// it is `None` on the last one.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ImagesResponse {
+    pub images: Vec<Image>,
+    pub next_cursor: Option<String>,
 }
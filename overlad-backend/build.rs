@@ -0,0 +1,4 @@
+fn main() {
+    // The migrations are embedded, so changing them needs a rebuild.
+    println!("cargo:rerun-if-changed=../migrations");
+}
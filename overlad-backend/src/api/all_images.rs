@@ -1,20 +1,16 @@
-use axum::{Json, extract::State, http::StatusCode};
-use overlad_api::Image;
+use axum::{Json, extract::State};
+use overlad_api::ImagesResponse;
 
-use crate::{AppState, db::image::DbImage, util::internal_server_error};
+use crate::{
+    AppState,
+    api::pagination::{ImagesQuery, list_images},
+    error::Result,
+    extract::Query,
+};
 
 pub async fn all_images(
     State(state): State<AppState>,
-) -> Result<Json<Vec<Image>>, (StatusCode, String)> {
-    let db_images = DbImage::get_all(&state.pool).await.unwrap();
-
-    let image_futures = db_images
-        .into_iter()
-        .map(|db_image| db_image.into_image(&state.pool));
-
-    let images = futures::future::try_join_all(image_futures)
-        .await
-        .map_err(internal_server_error)?;
-
-    Ok(Json(images))
+    Query(query): Query<ImagesQuery>,
+) -> Result<Json<ImagesResponse>> {
+    list_images(&state, None, query).await
 }
@@ -0,0 +1,34 @@
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderMap, header::CONTENT_TYPE},
+    response::IntoResponse,
+};
+
+use crate::{
+    AppState,
+    error::{Error, Result},
+    extract::Path,
+};
+
+pub async fn all_fonts(State(state): State<AppState>) -> Json<Vec<String>> {
+    Json(state.fonts.names().map(String::from).collect())
+}
+
+pub async fn get_font(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse> {
+    let font_file = state
+        .fonts
+        .get(&name)
+        .ok_or_else(|| Error::NotFound(format!("font {name} not found")))?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        CONTENT_TYPE,
+        format!("font/{}", font_file.extension).parse().unwrap(),
+    );
+
+    Ok((headers, font_file.bytes.clone()))
+}
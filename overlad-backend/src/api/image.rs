@@ -1,27 +1,72 @@
-use axum::{
-    Json,
-    extract::{Path, State},
-    http::StatusCode,
-};
+use axum::{Json, extract::State, http::StatusCode};
 use overlad_api::Image;
 
-use crate::{AppState, db::image::DbImage, util::internal_server_error};
+use crate::{
+    AppState,
+    auth::AuthUser,
+    db::rendition::DbRendition,
+    error::{Error, Result},
+    extract::Path,
+    rendition::original_key,
+};
 
 pub async fn get_image(
     State(state): State<AppState>,
     Path(id): Path<String>,
-) -> Result<Json<Image>, (StatusCode, String)> {
-    let maybe_db_image = DbImage::get_by_id(&state.pool, &id)
-        .await
-        .map_err(internal_server_error)?;
+) -> Result<Json<Image>> {
+    let maybe_db_image = state.db.get_image_by_id(&id).await?;
 
     let db_image =
-        maybe_db_image.ok_or((StatusCode::NOT_FOUND, format!("image {id} not found")))?;
+        maybe_db_image.ok_or_else(|| Error::NotFound(format!("image {id} not found")))?;
 
-    let image = db_image
-        .into_image(&state.pool)
-        .await
-        .map_err(internal_server_error)?;
+    let image = db_image.into_image(&*state.db).await?;
 
     Ok(Json(image))
 }
+
+/// Deletes an image owned by the caller.
+///
+/// Cached overlays are dropped first and the rows are deleted before the
+/// files, so a failure never leaves a row pointing at a missing file, only an
+/// orphaned file at worst. Every file is attempted and failures are logged
+/// rather than returned, since the image is already gone by then. A file that
+/// is already missing is not an error.
+pub async fn delete_image(
+    State(state): State<AppState>,
+    AuthUser(db_user): AuthUser,
+    Path(id): Path<String>,
+) -> Result<StatusCode> {
+    let db_image = state
+        .db
+        .get_image_by_id(&id)
+        .await?
+        .ok_or_else(|| Error::NotFound(format!("image {id} not found")))?;
+
+    if db_image.user_id != db_user.id {
+        return Err(Error::Forbidden(String::from(
+            "only the uploader can delete an image",
+        )));
+    }
+
+    let keys = state
+        .db
+        .get_renditions(&id)
+        .await?
+        .iter()
+        .map(DbRendition::key)
+        .chain([original_key(&id, &db_image.extension)])
+        .collect::<Vec<_>>();
+
+    state.overlay_cache.remove_prefix(&format!("{id}."));
+
+    // Renditions are deleted along with the image.
+    state.db.delete_image(&id).await?;
+
+    for key in keys {
+        if let Err(error) = state.storage.delete(&key).await {
+            tracing::warn!("failed to delete {key} of image {id}: {error}");
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,53 @@
+use axum::{extract::State, http::StatusCode};
+use chrono::Utc;
+use overlad_api::{ChangePasswordRequest, User};
+
+use crate::{
+    AppState, auth::AuthUser, db::user::hash_password, error::Result, extract::Json,
+    validation::FieldErrors,
+};
+
+pub async fn get_me(AuthUser(db_user): AuthUser) -> Json<User> {
+    Json(User::from(db_user))
+}
+
+pub async fn change_password(
+    State(state): State<AppState>,
+    AuthUser(db_user): AuthUser,
+    Json(change_password_request): Json<ChangePasswordRequest>,
+) -> Result<StatusCode> {
+    let old_password_matches = db_user
+        .verify_password(&change_password_request.old_password)
+        .await?;
+
+    let mut field_errors = FieldErrors::default();
+
+    field_errors.check(
+        "old_password",
+        (!old_password_matches).then(|| String::from("password is incorrect")),
+    );
+    field_errors.check(
+        "new_password",
+        state
+            .validation_rules
+            .validate_password(&change_password_request.new_password),
+    );
+    field_errors.check(
+        "confirm_password",
+        (change_password_request.new_password != change_password_request.confirm_password)
+            .then(|| String::from("passwords do not match")),
+    );
+
+    field_errors.finish()?;
+
+    state
+        .db
+        .update_password(
+            db_user.id,
+            &hash_password(&change_password_request.new_password).await?,
+            Utc::now().naive_utc(),
+        )
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,7 +1,11 @@
 pub mod all_images;
+pub mod fonts;
 pub mod image;
+pub mod me;
 pub mod overlay;
+pub mod pagination;
 pub mod register;
+pub mod renditions;
 pub mod token;
 pub mod upload;
 pub mod user;
@@ -1,106 +1,933 @@
-use std::io::Cursor;
+use std::{collections::BTreeMap, io::Cursor};
 
-use ab_glyph::FontRef;
 use axum::{
-    extract::{Path, Query},
-    http::{HeaderMap, StatusCode, header::CONTENT_TYPE},
-    response::IntoResponse,
+    extract::State,
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{ACCEPT, CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH, VARY},
+    },
+    response::{IntoResponse, Response},
+};
+use image::{
+    DynamicImage, Frame, ImageFormat, ImageResult, Rgba, RgbaImage,
+    codecs::{avif::AvifEncoder, jpeg::JpegEncoder},
+    imageops::{self, FilterType},
+};
+use overlad_api::DEFAULT_FONT;
+use overlad_lib::{
+    HorizontalAnchor, LayoutOptions, TextAlign, TextLayer, TextScale, VerticalAnchor,
+    animation::{decode_animation, encode_gif},
+    overlay,
 };
-use image::{ImageFormat, ImageReader, Rgba, imageops::FilterType};
-use overlad_lib::overlay;
 use serde::Deserialize;
 
-use crate::util::internal_server_error;
+use crate::{
+    AppState,
+    cache::RenderedOverlay,
+    db::rendition::DbRendition,
+    error::{Error, Result},
+    extract::{Path, Query},
+    rendition::original_key,
+    util::spawn_blocking,
+    validation::FieldErrors,
+};
+
+const MAX_LAYERS: usize = 16;
+const DEFAULT_MIN_TEXT_SCALE: f64 = 0.2;
+const DEFAULT_MAX_TEXT_SCALE: f64 = 5.0;
+const DEFAULT_QUALITY: u8 = 80;
+const AVIF_SPEED: u8 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Png,
+    #[serde(alias = "jpg")]
+    Jpeg,
+    Gif,
+    Webp,
+    Avif,
+}
+
+impl OutputFormat {
+    fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            "gif" => Some(Self::Gif),
+            "webp" => Some(Self::Webp),
+            "avif" => Some(Self::Avif),
+            _ => None,
+        }
+    }
+
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type {
+            "image/png" => Some(Self::Png),
+            "image/jpeg" => Some(Self::Jpeg),
+            "image/gif" => Some(Self::Gif),
+            "image/webp" => Some(Self::Webp),
+            "image/avif" => Some(Self::Avif),
+            _ => None,
+        }
+    }
+
+    fn media_type(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Gif => "image/gif",
+            Self::Webp => "image/webp",
+            Self::Avif => "image/avif",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpeg",
+            Self::Gif => "gif",
+            Self::Webp => "webp",
+            Self::Avif => "avif",
+        }
+    }
+}
+
+/// How the image is fitted when both `resize_width` and `resize_height` are
+/// given.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fit {
+    /// Scale to fit within the box, then pad to exactly its size.
+    Contain,
+    /// Scale to cover the box, then crop the overflow from the center.
+    Cover,
+    /// Stretch to exactly the box, ignoring the aspect ratio.
+    Fill,
+    /// Scale to fit within the box.
+    #[default]
+    Inside,
+}
 
 #[derive(Deserialize)]
 pub struct OverlayQuery {
+    font: Option<String>,
+    format: Option<OutputFormat>,
+    quality: Option<u8>,
+    resize_width: Option<u32>,
+    resize_height: Option<u32>,
+    fit: Option<Fit>,
+    upscale: Option<bool>,
+}
+
+/// The parameters of a single text layer.
+///
+/// Layers are encoded in the query string by suffixing each parameter with the
+/// layer index, e.g. `text[1]=bottom&vertical_anchor[1]=bottom`. Parameters
+/// without an index belong to layer 0.
+#[derive(Deserialize)]
+pub struct LayerQuery {
     text: Option<String>,
     text_color: Option<String>,
-    text_scale: Option<f64>,
+    text_scale: Option<String>,
+    min_text_scale: Option<f64>,
+    max_text_scale: Option<f64>,
     outline_color: Option<String>,
     outline_thickness: Option<f64>,
-    resize_width: Option<u32>,
-    resize_height: Option<u32>,
+    rotation: Option<f64>,
+    vertical_anchor: Option<VerticalAnchor>,
+    horizontal_anchor: Option<HorizontalAnchor>,
+    text_align: Option<TextAlign>,
+    max_width: Option<f64>,
+    max_height: Option<f64>,
+    line_spacing: Option<f64>,
+}
+
+impl LayerQuery {
+    /// Checks the parameters of layer `index`, rejecting text scales above
+    /// `max_text_scale`.
+    fn into_layer(self, index: usize, max_text_scale: f64) -> Result<TextLayer> {
+        let default_layer = TextLayer::default();
+        let default_layout = default_layer.layout;
+
+        let layout = LayoutOptions {
+            vertical_anchor: self
+                .vertical_anchor
+                .unwrap_or(default_layout.vertical_anchor),
+            horizontal_anchor: self
+                .horizontal_anchor
+                .unwrap_or(default_layout.horizontal_anchor),
+            text_align: self.text_align.unwrap_or(default_layout.text_align),
+            max_width: self.max_width.unwrap_or(default_layout.max_width),
+            max_height: self.max_height.unwrap_or(default_layout.max_height),
+            line_spacing: self.line_spacing.unwrap_or(default_layout.line_spacing),
+        };
+
+        if !(layout.max_width > 0.0 && layout.max_width <= 1.0) {
+            return Err(Error::BadRequest(String::from("bad max width")));
+        }
+
+        if !(layout.max_height > 0.0 && layout.max_height <= 1.0) {
+            return Err(Error::BadRequest(String::from("bad max height")));
+        }
+
+        if !(layout.line_spacing > 0.0 && layout.line_spacing <= 10.0) {
+            return Err(Error::BadRequest(String::from("bad line spacing")));
+        }
+
+        // Fields are named as in the query, with the layer index for all but
+        // the first layer.
+        let field = |name: &str| {
+            if index == 0 {
+                name.to_owned()
+            } else {
+                format!("{name}[{index}]")
+            }
+        };
+        let valid_scale = |scale: f64| scale.is_finite() && scale > 0.0 && scale <= max_text_scale;
+        let scale_message = || format!("must be greater than 0 and at most {max_text_scale}");
+
+        let mut field_errors = FieldErrors::default();
+
+        let scale = match self.text_scale.as_deref() {
+            None => default_layer.scale,
+            Some("auto") => {
+                let min = self.min_text_scale.unwrap_or(DEFAULT_MIN_TEXT_SCALE);
+                let max = self
+                    .max_text_scale
+                    .unwrap_or(DEFAULT_MAX_TEXT_SCALE.min(max_text_scale));
+
+                field_errors.check(
+                    &field("min_text_scale"),
+                    (!valid_scale(min)).then(scale_message),
+                );
+                field_errors.check(
+                    &field("max_text_scale"),
+                    if !valid_scale(max) {
+                        Some(scale_message())
+                    } else if max < min {
+                        Some(String::from("must not be less than min_text_scale"))
+                    } else {
+                        None
+                    },
+                );
+
+                TextScale::Auto { min, max }
+            }
+            Some(text_scale) => {
+                let scale = text_scale
+                    .parse()
+                    .map_err(|_| Error::BadRequest(String::from("bad text scale")))?;
+
+                field_errors.check(
+                    &field("text_scale"),
+                    (!valid_scale(scale)).then(scale_message),
+                );
+
+                TextScale::Fixed(scale)
+            }
+        };
+
+        field_errors.finish()?;
+
+        Ok(TextLayer {
+            text: self.text.unwrap_or_default(),
+            text_color: self
+                .text_color
+                .map(|text_color| parse_color(&text_color, "bad text color"))
+                .transpose()?
+                .unwrap_or(default_layer.text_color),
+            outline_color: self
+                .outline_color
+                .map(|outline_color| parse_color(&outline_color, "bad outline color"))
+                .transpose()?
+                .unwrap_or(default_layer.outline_color),
+            scale,
+            thickness: self.outline_thickness.unwrap_or(default_layer.thickness),
+            rotation: self.rotation.unwrap_or(default_layer.rotation),
+            layout,
+        })
+    }
 }
 
 pub async fn get_overlay(
-    Path(id): Path<String>,
+    State(state): State<AppState>,
+    Path(id_with_extension): Path<String>,
     Query(query): Query<OverlayQuery>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let text = query.text.unwrap_or_default();
-    let text_color = {
-        let text_color_vec = hex::decode(query.text_color.unwrap_or(String::from("ffffffff")))
-            .map_err(internal_server_error)?;
-        Rgba::from([
-            *text_color_vec
-                .first()
-                .ok_or((StatusCode::BAD_REQUEST, String::from("bad text color")))?,
-            *text_color_vec
-                .get(1)
-                .ok_or((StatusCode::BAD_REQUEST, String::from("bad text color")))?,
-            *text_color_vec
-                .get(2)
-                .ok_or((StatusCode::BAD_REQUEST, String::from("bad text color")))?,
-            *text_color_vec
-                .get(3)
-                .ok_or((StatusCode::BAD_REQUEST, String::from("bad text color")))?,
-        ])
+    Query(params): Query<Vec<(String, String)>>,
+    request_headers: HeaderMap,
+) -> Result<Response> {
+    let (id, path_format) = match id_with_extension.split_once('.') {
+        Some((id, extension)) => (
+            id,
+            Some(
+                OutputFormat::from_extension(extension)
+                    .ok_or_else(|| Error::BadRequest(format!("unsupported format {extension}")))?,
+            ),
+        ),
+        None => (id_with_extension.as_str(), None),
     };
-    let text_scale = query.text_scale.unwrap_or(1.0);
-    let outline_color = {
-        let outline_color_vec =
-            hex::decode(query.outline_color.unwrap_or(String::from("000000ff")))
-                .map_err(internal_server_error)?;
-        Rgba::from([
-            *outline_color_vec
-                .first()
-                .ok_or((StatusCode::BAD_REQUEST, String::from("bad outline color")))?,
-            *outline_color_vec
-                .get(1)
-                .ok_or((StatusCode::BAD_REQUEST, String::from("bad outline color")))?,
-            *outline_color_vec
-                .get(2)
-                .ok_or((StatusCode::BAD_REQUEST, String::from("bad outline color")))?,
-            *outline_color_vec
-                .get(3)
-                .ok_or((StatusCode::BAD_REQUEST, String::from("bad outline color")))?,
-        ])
-    };
-    let outline_thickness = query.outline_thickness.unwrap_or(0.0);
 
-    let dynamic_image = ImageReader::open(format!("images/{id}.webp"))
-        .unwrap()
-        .decode()
-        .unwrap();
+    // An explicit format wins over the Accept header, and `None` leaves the
+    // choice to the source: WebP for stills and GIF for animations.
+    let format = path_format
+        .or(query.format)
+        .or_else(|| request_headers.get(ACCEPT).and_then(format_from_accept));
 
-    let resized_image = if let Some(resize_width) = query.resize_width
-        && let Some(resize_height) = query.resize_height
-    {
-        dynamic_image.resize(resize_width, resize_height, FilterType::Lanczos3)
+    let cache_key = cache_key(id, format, &params)?;
+
+    let rendered = if let Some(rendered) = state.overlay_cache.get(&cache_key) {
+        rendered
     } else {
-        dynamic_image.clone()
+        let rendered = render_overlay(&state, id, format, query, params).await?;
+        state.overlay_cache.insert(cache_key, rendered.clone());
+        rendered
     };
 
-    let image = resized_image.into_rgba8();
-    let font = FontRef::try_from_slice(include_bytes!("../../../roboto.ttf")).unwrap();
-
-    let overlaid_image = overlay(
-        image,
-        text,
-        text_color,
-        outline_color,
-        text_scale,
-        outline_thickness,
-        font,
+    let mut headers = HeaderMap::new();
+    headers.insert(ETAG, rendered.etag.parse().unwrap());
+    headers.insert(
+        CACHE_CONTROL,
+        format!("public, max-age={}", state.overlay_max_age)
+            .parse()
+            .unwrap(),
     );
+    headers.insert(VARY, ACCEPT.into());
 
+    if let Some(if_none_match) = request_headers.get(IF_NONE_MATCH)
+        && etag_matches(if_none_match, &rendered.etag)
+    {
+        return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+    }
+
+    headers.insert(CONTENT_TYPE, rendered.content_type.parse().unwrap());
+
+    Ok((headers, rendered.body).into_response())
+}
+
+async fn render_overlay(
+    state: &AppState,
+    id: &str,
+    format: Option<OutputFormat>,
+    query: OverlayQuery,
+    params: Vec<(String, String)>,
+) -> Result<RenderedOverlay> {
+    let layers = parse_layers(params, state.max_text_scale)?;
+
+    let mut field_errors = FieldErrors::default();
+    for (field, maybe_dimension) in [
+        ("resize_width", query.resize_width),
+        ("resize_height", query.resize_height),
+    ] {
+        field_errors.check(
+            field,
+            maybe_dimension
+                .filter(|dimension| !(1..=state.max_output_dimension).contains(dimension))
+                .map(|_| format!("must be between 1 and {}", state.max_output_dimension)),
+        );
+    }
+    field_errors.finish()?;
+
+    let quality = query.quality.unwrap_or(DEFAULT_QUALITY);
+    if !(1..=100).contains(&quality) {
+        return Err(Error::BadRequest(String::from("bad quality")));
+    }
+
+    let font_name = query.font.as_deref().unwrap_or(DEFAULT_FONT);
+    let font_file = state
+        .fonts
+        .get(font_name)
+        .ok_or_else(|| Error::BadRequest(format!("font {font_name} not found")))?;
+
+    let db_image = state
+        .db
+        .get_image_by_id(id)
+        .await?
+        .ok_or_else(|| Error::NotFound(format!("image {id} not found")))?;
+
+    let renditions = state.db.get_renditions(id).await?;
+
+    // Images stored before renditions were kept are rendered from the stored
+    // file, which was normalized on upload back then.
+    let key = choose_rendition(&renditions, &query)
+        .map(DbRendition::key)
+        .unwrap_or_else(|| original_key(id, &db_image.extension));
+
+    // Failing to read or decode a stored image is our fault, not the client's.
+    let bytes = state.storage.get(&key).await.map_err(Error::internal)?;
+
+    let font = font_file.font.clone();
+    let upload_limits = state.upload_limits.clone();
+    let max_output_dimension = state.max_output_dimension;
+
+    spawn_blocking(move || {
+        let maybe_frames = if matches!(format, None | Some(OutputFormat::Gif)) {
+            decode_animation(&bytes, &upload_limits.animation_limits()).map_err(Error::internal)?
+        } else {
+            None
+        };
+
+        if let Some(frames) = maybe_frames {
+            if let Some(first_frame) = frames.first() {
+                let (width, height) = first_frame.buffer().dimensions();
+                let (output_width, output_height) = output_size(width, height, &query);
+
+                check_animation_size(
+                    output_width,
+                    output_height,
+                    frames.len(),
+                    upload_limits.max_animation_pixels,
+                )?;
+            }
+
+            let overlaid_frames = frames
+                .into_iter()
+                .map(|frame| {
+                    let delay = frame.delay();
+                    let image = resize(
+                        DynamicImage::from(frame.into_buffer()),
+                        &query,
+                        max_output_dimension,
+                    )?
+                    .into_rgba8();
+
+                    Ok(Frame::from_parts(
+                        overlay(image, &layers, &font),
+                        0,
+                        0,
+                        delay,
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let buf = encode_gif(overlaid_frames).map_err(Error::internal)?;
+
+            Ok(RenderedOverlay::new(OutputFormat::Gif.media_type(), buf))
+        } else {
+            // Stored stills are already upright, so their orientation is ignored.
+            let (dynamic_image, _) = image::guess_format(&bytes)
+                .and_then(|format| upload_limits.decode(&bytes, format))
+                .map_err(Error::internal)?;
+
+            let image = resize(dynamic_image, &query, max_output_dimension)?.into_rgba8();
+            let overlaid_image = overlay(image, &layers, &font);
+
+            let format = format.unwrap_or(OutputFormat::Webp);
+            let buf = encode_still(overlaid_image, format, quality).map_err(Error::internal)?;
+
+            Ok(RenderedOverlay::new(format.media_type(), buf))
+        }
+    })
+    .await
+}
+
+fn encode_still(image: RgbaImage, format: OutputFormat, quality: u8) -> ImageResult<Vec<u8>> {
     let mut buf = Cursor::new(Vec::new());
-    overlaid_image
-        .write_to(&mut buf, ImageFormat::WebP)
+
+    match format {
+        OutputFormat::Png => image.write_to(&mut buf, ImageFormat::Png)?,
+        OutputFormat::Jpeg => DynamicImage::from(image)
+            .into_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut buf, quality))?,
+        OutputFormat::Gif => image.write_to(&mut buf, ImageFormat::Gif)?,
+        OutputFormat::Webp => image.write_to(&mut buf, ImageFormat::WebP)?,
+        OutputFormat::Avif => image.write_with_encoder(AvifEncoder::new_with_speed_quality(
+            &mut buf, AVIF_SPEED, quality,
+        ))?,
+    }
+
+    Ok(buf.into_inner())
+}
+
+/// Picks the output format from an `Accept` header.
+///
+/// Returns `None` when the client accepts any image or both of the source
+/// formats, leaving the choice to the source. Otherwise the supported format
+/// with the highest quality value is used.
+fn format_from_accept(accept: &HeaderValue) -> Option<OutputFormat> {
+    let accept = accept.to_str().ok()?;
+
+    let mut media_types = accept
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';').map(str::trim);
+            let media_type = parts.next()?;
+            let quality = parts
+                .find_map(|part| part.strip_prefix("q="))
+                .and_then(|quality| quality.parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            (quality > 0.0).then_some((media_type, quality))
+        })
+        .collect::<Vec<(&str, f32)>>();
+
+    if media_types
+        .iter()
+        .any(|(media_type, _)| matches!(*media_type, "*/*" | "image/*"))
+    {
+        return None;
+    }
+
+    let accepts = |format: OutputFormat| {
+        media_types
+            .iter()
+            .any(|(media_type, _)| *media_type == format.media_type())
+    };
+
+    if accepts(OutputFormat::Webp) && accepts(OutputFormat::Gif) {
+        return None;
+    }
+
+    media_types.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    media_types
+        .into_iter()
+        .find_map(|(media_type, _)| OutputFormat::from_media_type(media_type))
+}
+
+/// Identifies a rendering by image id, output format and its query parameters
+/// in a canonical order, so equivalent URLs share a cache entry.
+fn cache_key(
+    id: &str,
+    format: Option<OutputFormat>,
+    params: &[(String, String)],
+) -> Result<String> {
+    let mut sorted_params = params.to_vec();
+    sorted_params.sort();
+
+    let encoded = serde_urlencoded::to_string(sorted_params).map_err(Error::internal)?;
+    let extension = format.map(OutputFormat::extension).unwrap_or("source");
+
+    Ok(format!("{id}.{extension}?{encoded}"))
+}
+
+fn etag_matches(if_none_match: &HeaderValue, etag: &str) -> bool {
+    if_none_match.to_str().is_ok_and(|if_none_match| {
+        if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+    })
+}
+
+/// Resizes the image to the requested box.
+///
+/// With a single dimension the other follows the aspect ratio. Unless
+/// `upscale` is set the image is never enlarged, though `contain` still pads
+/// to the full box. Rejects resizes to more than `max_output_dimension` on a
+/// side before any pixels are resampled.
+fn resize(
+    dynamic_image: DynamicImage,
+    query: &OverlayQuery,
+    max_output_dimension: u32,
+) -> Result<DynamicImage> {
+    let (width, height) = (dynamic_image.width(), dynamic_image.height());
+    let (output_width, output_height) = output_size(width, height, query);
+
+    if query.resize_width.is_some() || query.resize_height.is_some() {
+        check_output_size(output_width, output_height, query, max_output_dimension)?;
+    }
+
+    let (Some(target_width), Some(target_height)) = (query.resize_width, query.resize_height)
+    else {
+        return Ok(resize_exact(dynamic_image, output_width, output_height));
+    };
+
+    let resized = match query.fit.unwrap_or_default() {
+        Fit::Inside | Fit::Fill => resize_exact(dynamic_image, output_width, output_height),
+        Fit::Contain => {
+            let factor = fit_factor(width, height, query, f64::min);
+            let (resized_width, resized_height) = scaled_size(width, height, factor);
+            let resized = resize_exact(dynamic_image, resized_width, resized_height);
+
+            let mut canvas = RgbaImage::new(target_width, target_height);
+            imageops::overlay(
+                &mut canvas,
+                &resized.to_rgba8(),
+                (target_width as i64 - resized_width as i64) / 2,
+                (target_height as i64 - resized_height as i64) / 2,
+            );
+
+            DynamicImage::from(canvas)
+        }
+        Fit::Cover => {
+            // Cropping the source before scaling it never holds more than the
+            // output, unlike scaling the whole image first.
+            let factor = fit_factor(width, height, query, f64::max);
+            let crop_width = ((output_width as f64 / factor).round() as u32).clamp(1, width);
+            let crop_height = ((output_height as f64 / factor).round() as u32).clamp(1, height);
+
+            let cropped = dynamic_image.crop_imm(
+                (width - crop_width) / 2,
+                (height - crop_height) / 2,
+                crop_width,
+                crop_height,
+            );
+
+            resize_exact(cropped, output_width, output_height)
+        }
+    };
+
+    Ok(resized)
+}
+
+/// The size [`resize`] turns a `width` by `height` image into.
+fn output_size(width: u32, height: u32, query: &OverlayQuery) -> (u32, u32) {
+    let upscale = query.upscale.unwrap_or(false);
+    let clamp_factor = |factor: f64| if upscale { factor } else { factor.min(1.0) };
+
+    match (query.resize_width, query.resize_height) {
+        (None, None) => (width, height),
+        (Some(target_width), None) => scaled_size(
+            width,
+            height,
+            clamp_factor(target_width as f64 / width as f64),
+        ),
+        (None, Some(target_height)) => scaled_size(
+            width,
+            height,
+            clamp_factor(target_height as f64 / height as f64),
+        ),
+        (Some(target_width), Some(target_height)) => match query.fit.unwrap_or_default() {
+            Fit::Inside => scaled_size(width, height, fit_factor(width, height, query, f64::min)),
+            Fit::Contain => (target_width, target_height),
+            Fit::Cover => {
+                let (scaled_width, scaled_height) =
+                    scaled_size(width, height, fit_factor(width, height, query, f64::max));
+
+                (
+                    target_width.min(scaled_width),
+                    target_height.min(scaled_height),
+                )
+            }
+            Fit::Fill if upscale => (target_width, target_height),
+            Fit::Fill => (target_width.min(width), target_height.min(height)),
+        },
+    }
+}
+
+/// The factor fitting the image to the requested box, where `pick` chooses
+/// between the width and height factors.
+fn fit_factor(width: u32, height: u32, query: &OverlayQuery, pick: fn(f64, f64) -> f64) -> f64 {
+    let width_factor = query.resize_width.unwrap_or(width) as f64 / width as f64;
+    let height_factor = query.resize_height.unwrap_or(height) as f64 / height as f64;
+    let factor = pick(width_factor, height_factor);
+
+    if query.upscale.unwrap_or(false) {
+        factor
+    } else {
+        factor.min(1.0)
+    }
+}
+
+fn check_output_size(
+    output_width: u32,
+    output_height: u32,
+    query: &OverlayQuery,
+    max_output_dimension: u32,
+) -> Result<()> {
+    if output_width <= max_output_dimension && output_height <= max_output_dimension {
+        return Ok(());
+    }
+
+    let message = || {
+        Some(format!(
+            "resizes to {output_width}x{output_height}, more than {max_output_dimension} pixels on a side"
+        ))
+    };
+
+    let mut field_errors = FieldErrors::default();
+    if query.resize_width.is_some() {
+        field_errors.check("resize_width", message());
+    }
+    if query.resize_height.is_some() {
+        field_errors.check("resize_height", message());
+    }
+
+    field_errors.finish()
+}
+
+/// Rejects animations whose frames, which are all resized and held in memory
+/// before encoding, add up to more than `max_animation_pixels` once resized.
+fn check_animation_size(
+    output_width: u32,
+    output_height: u32,
+    frame_count: usize,
+    max_animation_pixels: u64,
+) -> Result<()> {
+    let output_pixels = u64::from(output_width) * u64::from(output_height) * frame_count as u64;
+
+    if output_pixels > max_animation_pixels {
+        return Err(Error::BadRequest(format!(
+            "resizes {frame_count} frames to {output_width}x{output_height}, more than {max_animation_pixels} pixels in total"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Picks the smallest rendition that can be resized to the requested size
+/// without upscaling, falling back to the full size one.
+///
+/// Renditions keep the aspect ratio of the full size one, so the output size
+/// is worked out against it.
+fn choose_rendition<'a>(
+    renditions: &'a [DbRendition],
+    query: &OverlayQuery,
+) -> Option<&'a DbRendition> {
+    let full = renditions.last()?;
+    let (width, height) = (full.width as f64, full.height as f64);
+
+    let width_factor = query
+        .resize_width
+        .map(|target_width| target_width as f64 / width);
+    let height_factor = query
+        .resize_height
+        .map(|target_height| target_height as f64 / height);
+
+    let factor = match (width_factor, height_factor) {
+        (None, None) => return Some(full),
+        (Some(factor), None) | (None, Some(factor)) => factor,
+        (Some(width_factor), Some(height_factor)) => match query.fit.unwrap_or_default() {
+            Fit::Contain | Fit::Inside => width_factor.min(height_factor),
+            Fit::Cover | Fit::Fill => width_factor.max(height_factor),
+        },
+    }
+    .min(1.0);
+
+    let needed_width = (width * factor).round() as i64;
+    let needed_height = (height * factor).round() as i64;
+
+    renditions
+        .iter()
+        .find(|rendition| rendition.width >= needed_width && rendition.height >= needed_height)
+        .or(Some(full))
+}
+
+fn scaled_size(width: u32, height: u32, factor: f64) -> (u32, u32) {
+    if factor == 1.0 {
+        return (width, height);
+    }
+
+    let scaled = |length: u32| ((length as f64 * factor).round() as u32).max(1);
+
+    (scaled(width), scaled(height))
+}
+
+fn resize_exact(dynamic_image: DynamicImage, width: u32, height: u32) -> DynamicImage {
+    if (width, height) == (dynamic_image.width(), dynamic_image.height()) {
+        return dynamic_image;
+    }
+
+    dynamic_image.resize_exact(width, height, FilterType::Lanczos3)
+}
+
+fn parse_layers(params: Vec<(String, String)>, max_text_scale: f64) -> Result<Vec<TextLayer>> {
+    let mut layer_params = BTreeMap::<usize, Vec<(String, String)>>::new();
+
+    for (key, value) in params {
+        let (name, index) = match split_layer_index(&key) {
+            Some((name, index)) => (name.to_owned(), index),
+            None => (key, 0),
+        };
+
+        layer_params.entry(index).or_default().push((name, value));
+    }
+
+    if layer_params.len() > MAX_LAYERS {
+        return Err(Error::BadRequest(format!(
+            "at most {MAX_LAYERS} layers are allowed"
+        )));
+    }
+
+    layer_params
+        .into_iter()
+        .map(|(index, params)| {
+            let encoded = serde_urlencoded::to_string(params).map_err(Error::internal)?;
+
+            serde_urlencoded::from_str::<LayerQuery>(&encoded)
+                .map_err(|error| Error::BadRequest(format!("{error}")))?
+                .into_layer(index, max_text_scale)
+        })
+        .collect()
+}
+
+fn split_layer_index(key: &str) -> Option<(&str, usize)> {
+    let (name, rest) = key.split_once('[')?;
+    let index = rest.strip_suffix(']')?.parse().ok()?;
+
+    Some((name, index))
+}
+
+fn parse_color(hex_color: &str, message: &str) -> Result<Rgba<u8>> {
+    let color_vec = hex::decode(hex_color).map_err(|_| Error::BadRequest(String::from(message)))?;
+
+    let color: [u8; 4] = color_vec
+        .try_into()
+        .map_err(|_| Error::BadRequest(String::from(message)))?;
+
+    Ok(Rgba(color))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(query: &str) -> OverlayQuery {
+        serde_urlencoded::from_str(query).unwrap()
+    }
+
+    fn params(params: &[(&str, &str)]) -> Vec<(String, String)> {
+        params
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn cache_keys_ignore_the_order_of_parameters() {
+        let key =
+            |format, pairs: &[(&str, &str)]| cache_key("abc", format, &params(pairs)).unwrap();
+
+        assert_eq!(
+            key(None, &[("text", "a b"), ("font", "x")]),
+            key(None, &[("font", "x"), ("text", "a b")])
+        );
+        assert_eq!(key(None, &[("text", "a b")]), "abc.source?text=a+b");
+        assert_eq!(key(Some(OutputFormat::Png), &[]), "abc.png?");
+        assert_ne!(
+            key(None, &[("text[0]", "a"), ("text[1]", "b")]),
+            key(None, &[("text[0]", "b"), ("text[1]", "a")])
+        );
+    }
+
+    #[test]
+    fn accept_picks_the_supported_format_with_the_highest_quality() {
+        let format = |accept: &'static str| format_from_accept(&HeaderValue::from_static(accept));
+
+        assert_eq!(format("image/png"), Some(OutputFormat::Png));
+        assert_eq!(
+            format("image/png;q=0.5, image/jpeg;q=0.9"),
+            Some(OutputFormat::Jpeg)
+        );
+        assert_eq!(
+            format("text/html, image/avif; q=0.1"),
+            Some(OutputFormat::Avif)
+        );
+        assert_eq!(format("image/avif;q=0, image/png"), Some(OutputFormat::Png));
+        assert_eq!(format("text/html, image/bmp"), None);
+    }
+
+    #[test]
+    fn accepting_any_image_leaves_the_format_to_the_source() {
+        let format = |accept: &'static str| format_from_accept(&HeaderValue::from_static(accept));
+
+        assert_eq!(format("*/*"), None);
+        assert_eq!(format("image/png, image/*;q=0.8"), None);
+        assert_eq!(format("image/webp, image/gif"), None);
+        assert_eq!(format("image/png, */*;q=0"), Some(OutputFormat::Png));
+    }
+
+    #[test]
+    fn etags_match_any_listed_tag_weak_or_strong() {
+        let matches = |if_none_match: &'static str| {
+            etag_matches(&HeaderValue::from_static(if_none_match), "\"a\"")
+        };
+
+        assert!(matches("\"a\""));
+        assert!(matches("W/\"a\""));
+        assert!(matches("\"b\", \"a\""));
+        assert!(matches("*"));
+        assert!(!matches("\"b\""));
+        assert!(!matches("a"));
+    }
+
+    #[test]
+    fn output_size_follows_the_aspect_ratio_of_a_single_dimension() {
+        assert_eq!(output_size(400, 200, &query("resize_width=100")), (100, 50));
+        assert_eq!(
+            output_size(400, 200, &query("resize_height=100")),
+            (200, 100)
+        );
+        assert_eq!(
+            output_size(400, 200, &query("resize_width=800")),
+            (400, 200)
+        );
+        assert_eq!(
+            output_size(10, 4000, &query("resize_width=4096&upscale=true")),
+            (4096, 1_638_400)
+        );
+    }
+
+    #[test]
+    fn output_size_fits_both_dimensions() {
+        let size = |fit: &str| {
+            output_size(
+                400,
+                200,
+                &query(&format!("resize_width=100&resize_height=100&fit={fit}")),
+            )
+        };
+
+        assert_eq!(size("inside"), (100, 50));
+        assert_eq!(size("contain"), (100, 100));
+        assert_eq!(size("cover"), (100, 100));
+        assert_eq!(size("fill"), (100, 100));
+        assert_eq!(
+            output_size(
+                10,
+                4000,
+                &query("resize_width=4096&resize_height=10&fit=cover&upscale=true")
+            ),
+            (4096, 10)
+        );
+    }
+
+    #[test]
+    fn resize_rejects_outputs_larger_than_the_maximum() {
+        let image = DynamicImage::new_rgba8(10, 400);
+
+        let Err(Error::Validation(fields)) =
+            resize(image.clone(), &query("resize_width=100&upscale=true"), 1024)
+        else {
+            panic!("a 100x4000 output was allowed");
+        };
+        assert_eq!(fields.keys().collect::<Vec<_>>(), ["resize_width"]);
+
+        assert!(resize(image, &query("resize_width=100"), 1024).is_ok());
+    }
+
+    #[test]
+    fn resize_covers_without_scaling_the_whole_image() {
+        let image = DynamicImage::new_rgba8(10, 400);
+
+        let resized = resize(
+            image,
+            &query("resize_width=1000&resize_height=10&fit=cover&upscale=true"),
+            1024,
+        )
         .unwrap();
 
-    let mut headers = HeaderMap::new();
-    headers.insert(CONTENT_TYPE, "image/webp".parse().unwrap());
+        assert_eq!((resized.width(), resized.height()), (1000, 10));
+    }
+
+    #[test]
+    fn resize_matches_output_size() {
+        let image = DynamicImage::new_rgba8(300, 200);
+
+        for fit in ["inside", "contain", "cover", "fill"] {
+            for upscale in [false, true] {
+                let query = query(&format!(
+                    "resize_width=450&resize_height=120&fit={fit}&upscale={upscale}"
+                ));
+                let resized = resize(image.clone(), &query, 1024).unwrap();
 
-    Ok((headers, buf.into_inner()))
+                assert_eq!(
+                    (resized.width(), resized.height()),
+                    output_size(300, 200, &query),
+                    "{fit} with upscale={upscale}"
+                );
+            }
+        }
+    }
 }
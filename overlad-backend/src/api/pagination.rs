@@ -0,0 +1,92 @@
+use axum::Json;
+use base64::prelude::*;
+use chrono::NaiveDateTime;
+use overlad_api::{Image, ImageSort, ImagesResponse};
+use serde::Deserialize;
+
+use crate::{
+    AppState,
+    db::image::ImageCursor,
+    error::{Error, Result},
+};
+
+const DEFAULT_LIMIT: u32 = 24;
+const MAX_LIMIT: u32 = 100;
+const CURSOR_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+
+#[derive(Deserialize)]
+pub struct ImagesQuery {
+    limit: Option<u32>,
+    cursor: Option<String>,
+    sort: Option<ImageSort>,
+}
+
+/// Lists a page of images, all of them or only those of `user_id`.
+///
+/// One more row than asked for is fetched to tell whether there is a next
+/// page without a separate count.
+pub async fn list_images(
+    state: &AppState,
+    user_id: Option<i64>,
+    query: ImagesQuery,
+) -> Result<Json<ImagesResponse>> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(Error::BadRequest(format!(
+            "limit must be between 1 and {MAX_LIMIT}"
+        )));
+    }
+
+    let cursor = query.cursor.as_deref().map(decode_cursor).transpose()?;
+
+    let mut db_images = state
+        .db
+        .get_image_page(
+            user_id,
+            query.sort.unwrap_or_default(),
+            cursor.as_ref(),
+            limit + 1,
+        )
+        .await?;
+
+    let next_cursor = if db_images.len() > limit as usize {
+        db_images.truncate(limit as usize);
+        db_images
+            .last()
+            .map(|db_image| encode_cursor(&db_image.cursor()))
+    } else {
+        None
+    };
+
+    Ok(Json(ImagesResponse {
+        images: db_images.into_iter().map(Image::from).collect(),
+        next_cursor,
+    }))
+}
+
+fn encode_cursor(cursor: &ImageCursor) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(format!(
+        "{}\n{}",
+        cursor.created_at.format(CURSOR_TIME_FORMAT),
+        cursor.id
+    ))
+}
+
+fn decode_cursor(encoded: &str) -> Result<ImageCursor> {
+    let bad_cursor = || Error::BadRequest(String::from("bad cursor"));
+
+    let decoded = BASE64_URL_SAFE_NO_PAD
+        .decode(encoded)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or_else(bad_cursor)?;
+
+    let (created_at, id) = decoded.split_once('\n').ok_or_else(bad_cursor)?;
+
+    Ok(ImageCursor {
+        created_at: NaiveDateTime::parse_from_str(created_at, CURSOR_TIME_FORMAT)
+            .map_err(|_| bad_cursor())?,
+        id: id.to_owned(),
+    })
+}
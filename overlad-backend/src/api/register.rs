@@ -1,8 +1,16 @@
-use axum::{Json, extract::State, http::StatusCode};
+use axum::extract::State;
 use overlad_api::User;
 use serde::Deserialize;
 
-use crate::{AppState, db::user::DbUser, util::internal_server_error};
+use crate::{
+    AppState,
+    client_ip::ClientIp,
+    db::user::hash_password,
+    error::{Error, Result},
+    extract::Json,
+    rate_limit::rate_limit_keys,
+    validation::FieldErrors,
+};
 
 #[derive(Deserialize)]
 pub struct RegisterRequest {
@@ -11,32 +19,71 @@ pub struct RegisterRequest {
     confirm_password: String,
 }
 
+/// Creates an account. Shares the login rate limits, and probing for taken
+/// usernames counts as a failure.
 pub async fn register(
     State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
     Json(register_request): Json<RegisterRequest>,
-) -> Result<Json<User>, (StatusCode, String)> {
-    let maybe_db_user = DbUser::get_by_username(&state.pool, &register_request.username)
-        .await
-        .map_err(internal_server_error)?;
+) -> Result<Json<User>> {
+    let keys = rate_limit_keys(client_ip, &register_request.username);
+
+    state.auth_rate_limiter.check(&keys)?;
+
+    let mut field_errors = FieldErrors::default();
+
+    field_errors.check(
+        "username",
+        state
+            .validation_rules
+            .validate_username(&register_request.username),
+    );
+    field_errors.check(
+        "password",
+        state
+            .validation_rules
+            .validate_password(&register_request.password),
+    );
+    field_errors.check(
+        "confirm_password",
+        (register_request.password != register_request.confirm_password)
+            .then(|| String::from("passwords do not match")),
+    );
+
+    field_errors.finish()?;
+
+    let maybe_db_user = state
+        .db
+        .get_user_by_username(&register_request.username)
+        .await?;
 
     if maybe_db_user.is_some() {
-        return Err((StatusCode::CONFLICT, String::from("username is taken")));
-    }
+        state.auth_rate_limiter.fail(&keys);
 
-    if register_request.password != register_request.confirm_password {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            String::from("passwords do not match"),
-        ));
+        return Err(username_taken());
     }
 
-    let db_user = DbUser::insert(
-        &state.pool,
-        &register_request.username,
-        &register_request.password,
-    )
-    .await
-    .map_err(internal_server_error)?;
+    let passhash = hash_password(&register_request.password).await?;
+
+    // A concurrent registration of the same username can still win the race
+    // past the check above, leaving the unique constraint to catch it.
+    let db_user = match state
+        .db
+        .insert_user(&register_request.username, &passhash)
+        .await
+    {
+        Ok(db_user) => db_user,
+        Err(sqlx::Error::Database(error)) if error.is_unique_violation() => {
+            state.auth_rate_limiter.fail(&keys);
+
+            return Err(username_taken());
+        }
+        Err(error) => return Err(error.into()),
+    };
 
     Ok(Json(User::from(db_user)))
 }
+
+fn username_taken() -> Error {
+    Error::Conflict(String::from("username is taken"))
+}
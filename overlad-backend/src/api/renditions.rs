@@ -0,0 +1,13 @@
+use axum::{Json, extract::State};
+
+use crate::AppState;
+
+/// The widths renditions are generated at, narrowest first. Overlays resized
+/// to one of them are rendered from that rendition.
+pub async fn rendition_widths(State(state): State<AppState>) -> Json<Vec<u32>> {
+    let mut widths = state.rendition_rules.widths.clone();
+    widths.sort_unstable();
+    widths.dedup();
+
+    Json(widths)
+}
@@ -1,31 +1,79 @@
-use axum::{Json, extract::State, http::StatusCode};
-use jwt::SignWithKey;
-use overlad_api::{TokenClaims, TokenRequest};
+use axum::{extract::State, http::StatusCode};
+use chrono::Utc;
+use overlad_api::{RefreshTokenRequest, TokenRequest, TokenResponse};
 
-use crate::{AppState, db::user::DbUser, util::internal_server_error};
+use crate::{
+    AppState,
+    auth::{hash_refresh_token, issue_tokens},
+    client_ip::ClientIp,
+    error::{Error, Result},
+    extract::Json,
+    rate_limit::rate_limit_keys,
+};
 
+/// Logs in with a username and password. Attempts are rate limited per client
+/// IP and per username, and repeated failures lock both out for a while.
 pub async fn token(
     State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
     Json(token_request): Json<TokenRequest>,
-) -> Result<(StatusCode, String), (StatusCode, String)> {
-    let maybe_user = DbUser::get_by_username(&state.pool, &token_request.username)
-        .await
-        .map_err(internal_server_error)?;
+) -> Result<Json<TokenResponse>> {
+    let keys = rate_limit_keys(client_ip, &token_request.username);
 
-    if let Some(user) = maybe_user
-        && user.verify_password(&token_request.password)
-    {
-        let token_claims = TokenClaims { sub: user.id };
+    state.auth_rate_limiter.check(&keys)?;
 
-        let token = token_claims
-            .sign_with_key(&state.key)
-            .map_err(internal_server_error)?;
+    let maybe_user = state
+        .db
+        .get_user_by_username(&token_request.username)
+        .await?;
 
-        Ok((StatusCode::OK, token))
+    let maybe_verified_user = match maybe_user {
+        Some(user) if user.verify_password(&token_request.password).await? => Some(user),
+        _ => None,
+    };
+
+    if let Some(user) = maybe_verified_user {
+        state.auth_rate_limiter.succeed(&keys);
+
+        Ok(Json(issue_tokens(&state, user.id).await?))
     } else {
-        Err((
-            StatusCode::UNAUTHORIZED,
-            String::from("username or password not found"),
-        ))
+        state.auth_rate_limiter.fail(&keys);
+
+        Err(Error::Unauthorized(String::from(
+            "username or password not found",
+        )))
     }
 }
+
+/// Exchanges a refresh token for a new access token and refresh token. The
+/// old refresh token is revoked, so each one can only be used once.
+pub async fn refresh(
+    State(state): State<AppState>,
+    Json(refresh_token_request): Json<RefreshTokenRequest>,
+) -> Result<Json<TokenResponse>> {
+    let user_id = state
+        .db
+        .consume_refresh_token(
+            &hash_refresh_token(&refresh_token_request.refresh_token),
+            Utc::now().naive_utc(),
+        )
+        .await?
+        .ok_or_else(|| Error::Unauthorized(String::from("refresh token is invalid or expired")))?;
+
+    Ok(Json(issue_tokens(&state, user_id).await?))
+}
+
+pub async fn logout(
+    State(state): State<AppState>,
+    Json(refresh_token_request): Json<RefreshTokenRequest>,
+) -> Result<StatusCode> {
+    state
+        .db
+        .revoke_refresh_token(
+            &hash_refresh_token(&refresh_token_request.refresh_token),
+            Utc::now().naive_utc(),
+        )
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,53 +1,209 @@
 use axum::{Json, body::Bytes, extract::State, http::StatusCode};
-use axum_extra::{
-    TypedHeader,
-    headers::{Authorization, authorization::Bearer},
+use axum_typed_multipart::{
+    FieldData, TryFromField, TryFromMultipart, TypedMultipart, TypedMultipartError,
 };
-use axum_typed_multipart::{TryFromMultipart, TypedMultipart};
 use base64::prelude::*;
-use jwt::VerifyWithKey;
-use overlad_api::{Image, TokenClaims};
+use image::DynamicImage;
+use overlad_api::Image;
+use overlad_lib::metadata::strip_metadata;
 
-use crate::{AppState, db::image::DbImage, util::internal_server_error};
+use crate::{
+    AppState,
+    auth::AuthUser,
+    db::image::ImageMetadata,
+    error::{Error, Result},
+    rendition::{EncodedRendition, original_key, rendition_key},
+    util::spawn_blocking,
+    validation::FieldErrors,
+};
 
 #[derive(TryFromMultipart)]
 pub struct UploadMultipart {
-    image: Bytes,
+    image: FieldData<Bytes>,
+
+    /// Clockwise rotation applied after the EXIF orientation.
+    rotate: Option<Rotation>,
+
+    /// Region kept after rotating, in pixels of the upright image.
+    crop_x: Option<u32>,
+    crop_y: Option<u32>,
+    crop_width: Option<u32>,
+    crop_height: Option<u32>,
 }
 
+#[derive(Debug, Clone, Copy, TryFromField)]
+pub enum Rotation {
+    #[field(rename = "90")]
+    Clockwise90,
+    #[field(rename = "180")]
+    Clockwise180,
+    #[field(rename = "270")]
+    Clockwise270,
+}
+
+/// Stores an uploaded image.
+///
+/// The upload is kept untouched as the original, which is never served. What
+/// is served is rendered from renditions derived from it. For stills these are
+/// decoded, turned upright according to their EXIF orientation, optionally
+/// rotated and cropped, and re-encoded at several widths. The encoders write
+/// no metadata, so EXIF data such as GPS coordinates never reaches a rendition.
+/// Animations get a single full size rendition, which is the upload with its
+/// metadata stripped, and cannot be rotated or cropped.
 pub async fn upload(
     State(state): State<AppState>,
-    TypedHeader(authorization): TypedHeader<Authorization<Bearer>>,
-    multipart: TypedMultipart<UploadMultipart>,
-) -> Result<Json<Image>, (StatusCode, String)> {
-    let maybe_token_claims: Result<TokenClaims, jwt::Error> =
-        authorization.token().verify_with_key(&state.key);
+    AuthUser(db_user): AuthUser,
+    multipart: std::result::Result<TypedMultipart<UploadMultipart>, TypedMultipartError>,
+) -> Result<Json<Image>> {
+    // The body limit surfaces as a multipart error, which would otherwise be
+    // sent as plain text.
+    let multipart = multipart.map_err(|error| {
+        if error.get_status() == StatusCode::PAYLOAD_TOO_LARGE {
+            state.upload_limits.too_many_bytes()
+        } else {
+            Error::BadRequest(format!("{error}"))
+        }
+    })?;
+
+    let mut id_bytes = [0u8; 32];
+    rand::fill(&mut id_bytes);
+
+    let id = BASE64_URL_SAFE_NO_PAD.encode(id_bytes);
+
+    let bytes = multipart.image.contents.clone();
+    let file_name = multipart.image.metadata.file_name.clone();
+    let upload_limits = state.upload_limits.clone();
+    let rendition_rules = state.rendition_rules.clone();
+
+    let (extension, renditions) = spawn_blocking(move || {
+        let bytes = &multipart.image.contents;
+
+        let checked_upload = upload_limits.check(bytes)?;
+
+        let transformed = multipart.rotate.is_some()
+            || multipart.crop_x.is_some()
+            || multipart.crop_y.is_some()
+            || multipart.crop_width.is_some()
+            || multipart.crop_height.is_some();
+
+        let extension = checked_upload.format.extensions_str()[0];
+
+        // Animations are not re-encoded since that would drop all but the first
+        // frame.
+        let renditions = if checked_upload.animated {
+            if transformed {
+                return Err(Error::BadRequest(String::from(
+                    "animations cannot be rotated or cropped",
+                )));
+            }
+
+            vec![EncodedRendition {
+                width: checked_upload.width,
+                height: checked_upload.height,
+                extension,
+                bytes: strip_metadata(bytes, checked_upload.format)?,
+            }]
+        } else {
+            let (mut image, orientation) = upload_limits.decode(bytes, checked_upload.format)?;
 
-    if let Ok(token_claims) = maybe_token_claims {
-        let image = image::load_from_memory(&multipart.image).unwrap();
+            image.apply_orientation(orientation);
 
-        let mut id_bytes = [0u8; 32];
-        rand::fill(&mut id_bytes);
+            let image = crop(rotate(image, multipart.rotate), &multipart)?;
 
-        let id = BASE64_URL_SAFE_NO_PAD.encode(id_bytes);
-        let extension = "webp";
+            rendition_rules.render(&image).map_err(Error::internal)?
+        };
 
-        image.save(format!("images/{id}.{extension}")).unwrap();
+        Ok((extension, renditions))
+    })
+    .await?;
 
-        let db_image = DbImage::insert(&state.pool, &id, token_claims.sub, extension)
+    state
+        .storage
+        .put(&original_key(&id, extension), bytes.to_vec())
+        .await
+        .map_err(Error::internal)?;
+
+    for rendition in &renditions {
+        state
+            .storage
+            .put(
+                &rendition_key(&id, rendition.width, rendition.extension),
+                rendition.bytes.clone(),
+            )
             .await
-            .unwrap();
-
-        Ok(Json(
-            db_image
-                .into_image(&state.pool)
-                .await
-                .map_err(internal_server_error)?,
-        ))
+            .map_err(Error::internal)?;
+    }
+
+    // The first rendition is the full size one, so its dimensions are those
+    // of the image as served.
+    let metadata = ImageMetadata {
+        width: renditions[0].width,
+        height: renditions[0].height,
+        size_bytes: bytes.len() as u64,
+    };
+
+    let db_image = state
+        .db
+        .insert_image(
+            &id,
+            db_user.id,
+            extension,
+            &metadata,
+            file_name.as_deref(),
+            &renditions,
+        )
+        .await?;
+
+    Ok(Json(db_image.into_image(&*state.db).await?))
+}
+
+fn rotate(image: DynamicImage, maybe_rotation: Option<Rotation>) -> DynamicImage {
+    match maybe_rotation {
+        Some(Rotation::Clockwise90) => image.rotate90(),
+        Some(Rotation::Clockwise180) => image.rotate180(),
+        Some(Rotation::Clockwise270) => image.rotate270(),
+        None => image,
+    }
+}
+
+/// Crops to the requested region, which defaults to the whole image on any
+/// side that is left out but must otherwise lie within it.
+fn crop(image: DynamicImage, multipart: &UploadMultipart) -> Result<DynamicImage> {
+    let x = multipart.crop_x.unwrap_or(0);
+    let y = multipart.crop_y.unwrap_or(0);
+    let width = multipart
+        .crop_width
+        .unwrap_or(image.width().saturating_sub(x));
+    let height = multipart
+        .crop_height
+        .unwrap_or(image.height().saturating_sub(y));
+
+    let mut field_errors = FieldErrors::default();
+
+    field_errors.check(
+        "crop_width",
+        (width == 0 || u64::from(x) + u64::from(width) > u64::from(image.width())).then(|| {
+            format!(
+                "crop must be at least 1 pixel wide and within the image width of {}",
+                image.width()
+            )
+        }),
+    );
+    field_errors.check(
+        "crop_height",
+        (height == 0 || u64::from(y) + u64::from(height) > u64::from(image.height())).then(|| {
+            format!(
+                "crop must be at least 1 pixel tall and within the image height of {}",
+                image.height()
+            )
+        }),
+    );
+
+    field_errors.finish()?;
+
+    if (x, y, width, height) == (0, 0, image.width(), image.height()) {
+        Ok(image)
     } else {
-        Err((
-            StatusCode::UNAUTHORIZED,
-            String::from("could not verify token"),
-        ))
+        Ok(image.crop_imm(x, y, width, height))
     }
 }
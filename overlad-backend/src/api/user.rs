@@ -1,20 +1,21 @@
-use axum::{
-    Json,
-    extract::{Path, State},
-    http::StatusCode,
-};
+use axum::{Json, extract::State};
 use overlad_api::User;
 
-use crate::{db::user::DbUser, util::{internal_server_error, to_row_not_found}, AppState};
+use crate::{
+    AppState,
+    error::{Error, Result},
+    extract::Path,
+};
 
 pub async fn get_user(
     State(state): State<AppState>,
     Path(user_id): Path<i64>,
-) -> Result<Json<User>, (StatusCode, String)> {
-    let db_user = DbUser::get_by_id(&state.pool, user_id)
-        .await
-        .and_then(to_row_not_found)
-        .map_err(internal_server_error)?;
+) -> Result<Json<User>> {
+    let db_user = state
+        .db
+        .get_user_by_id(user_id)
+        .await?
+        .ok_or_else(|| Error::NotFound(format!("user {user_id} not found")))?;
 
     Ok(Json(User::from(db_user)))
 }
@@ -1,27 +1,17 @@
-use axum::{
-    Json,
-    extract::{Path, State},
-    http::StatusCode,
-};
-use overlad_api::Image;
+use axum::{Json, extract::State};
+use overlad_api::ImagesResponse;
 
-use crate::{AppState, db::image::DbImage, util::internal_server_error};
+use crate::{
+    AppState,
+    api::pagination::{ImagesQuery, list_images},
+    error::Result,
+    extract::{Path, Query},
+};
 
 pub async fn user_images(
     State(state): State<AppState>,
     Path(user_id): Path<i64>,
-) -> Result<Json<Vec<Image>>, (StatusCode, String)> {
-    let db_images = DbImage::get_by_user_id(&state.pool, user_id)
-        .await
-        .map_err(internal_server_error)?;
-
-    let image_futures = db_images
-        .into_iter()
-        .map(|db_image| db_image.into_image(&state.pool));
-
-    let images = futures::future::try_join_all(image_futures)
-        .await
-        .map_err(internal_server_error)?;
-
-    Ok(Json(images))
+    Query(query): Query<ImagesQuery>,
+) -> Result<Json<ImagesResponse>> {
+    list_images(&state, Some(user_id), query).await
 }
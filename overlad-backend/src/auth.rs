@@ -0,0 +1,122 @@
+use axum::{
+    RequestPartsExt,
+    extract::{FromRequestParts, OptionalFromRequestParts},
+    http::{header::AUTHORIZATION, request::Parts},
+};
+use axum_extra::{
+    TypedHeader,
+    headers::{Authorization, authorization::Bearer},
+};
+use base64::prelude::*;
+use chrono::{TimeDelta, Utc};
+use jwt::{SignWithKey, VerifyWithKey};
+use overlad_api::{TokenClaims, TokenResponse};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    AppState,
+    db::user::DbUser,
+    error::{Error, Result},
+};
+
+/// The user a request is authenticated as, taken from its bearer token.
+///
+/// Rejects with 401 when the token is missing, invalid or expired, or its user
+/// no longer exists. Extract `Option<AuthUser>` for routes that also serve
+/// anonymous requests, which is `None` only when there is no token at all.
+pub struct AuthUser(pub DbUser);
+
+impl FromRequestParts<AppState> for AuthUser {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self> {
+        let TypedHeader(authorization) = parts
+            .extract::<TypedHeader<Authorization<Bearer>>>()
+            .await
+            .map_err(|_| Error::Unauthorized(String::from("missing bearer token")))?;
+
+        let token_claims = verify_access_token(state, authorization.token())?;
+
+        let db_user = state
+            .db
+            .get_user_by_id(token_claims.sub)
+            .await?
+            .ok_or_else(|| Error::Unauthorized(String::from("user no longer exists")))?;
+
+        Ok(Self(db_user))
+    }
+}
+
+impl OptionalFromRequestParts<AppState> for AuthUser {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Option<Self>> {
+        if parts.headers.contains_key(AUTHORIZATION) {
+            <Self as FromRequestParts<AppState>>::from_request_parts(parts, state)
+                .await
+                .map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Signs a short-lived access token for `user_id` and stores a refresh token
+/// that can be exchanged for the next one.
+pub async fn issue_tokens(state: &AppState, user_id: i64) -> Result<TokenResponse> {
+    let now = Utc::now();
+
+    let token_claims = TokenClaims {
+        sub: user_id,
+        iat: now.timestamp(),
+        exp: (now + TimeDelta::seconds(state.access_token_ttl as i64)).timestamp(),
+        jti: random_token(16),
+    };
+
+    let token = token_claims
+        .sign_with_key(&state.key)
+        .map_err(Error::internal)?;
+
+    let refresh_token = random_token(32);
+
+    state
+        .db
+        .insert_refresh_token(
+            &hash_refresh_token(&refresh_token),
+            user_id,
+            now.naive_utc(),
+            (now + TimeDelta::seconds(state.refresh_token_ttl as i64)).naive_utc(),
+        )
+        .await?;
+
+    Ok(TokenResponse {
+        token,
+        refresh_token,
+    })
+}
+
+/// Checks the signature and expiry of an access token.
+fn verify_access_token(state: &AppState, token: &str) -> Result<TokenClaims> {
+    let token_claims: TokenClaims = token
+        .verify_with_key(&state.key)
+        .map_err(|_| Error::Unauthorized(String::from("could not verify token")))?;
+
+    if token_claims.exp <= Utc::now().timestamp() {
+        return Err(Error::Unauthorized(String::from("token expired")));
+    }
+
+    Ok(token_claims)
+}
+
+/// Refresh tokens are only stored hashed, so a leaked database cannot be used
+/// to sign in.
+pub fn hash_refresh_token(refresh_token: &str) -> String {
+    hex::encode(Sha256::digest(refresh_token))
+}
+
+fn random_token(length: usize) -> String {
+    let mut bytes = vec![0u8; length];
+    rand::fill(bytes.as_mut_slice());
+
+    BASE64_URL_SAFE_NO_PAD.encode(bytes)
+}
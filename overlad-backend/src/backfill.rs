@@ -0,0 +1,149 @@
+use std::io::{self, Cursor};
+
+use image::{ImageReader, ImageResult};
+use overlad_lib::{animation::is_animated, metadata::strip_metadata};
+
+use crate::{
+    db::{Repository, image::ImageMetadata},
+    rendition::{EncodedRendition, RenditionRules, original_key, rendition_key},
+    storage::Storage,
+    upload_limits::UploadLimits,
+};
+
+/// Records the dimensions, size and upload time of images stored before that
+/// metadata was kept, reading each file from storage.
+///
+/// The upload time is taken from the file's modification time. Files that
+/// cannot be read are reported and skipped so the rest can still be filled in.
+pub async fn backfill(db: &dyn Repository, storage: &dyn Storage) -> sqlx::Result<()> {
+    let db_images = db.get_images_missing_metadata().await?;
+    let mut updated = 0;
+
+    for db_image in &db_images {
+        let key = original_key(&db_image.id, &db_image.extension);
+
+        // Images whose files were lost are common enough in old installs to
+        // report plainly.
+        if let Ok(false) = storage.exists(&key).await {
+            tracing::error!("skipping {key}: missing from storage");
+            continue;
+        }
+
+        let metadata = match storage.get(&key).await {
+            Ok(bytes) => ImageMetadata::from_bytes(&bytes),
+            Err(error) => Err(error.into()),
+        };
+
+        let metadata = match metadata {
+            Ok(metadata) => metadata,
+            Err(error) => {
+                tracing::error!("skipping {key}: {error}");
+                continue;
+            }
+        };
+
+        let created_at = storage
+            .last_modified(&key)
+            .await
+            .map(|last_modified| last_modified.naive_utc())
+            .unwrap_or(db_image.created_at);
+
+        db.update_image_metadata(&db_image.id, &metadata, created_at)
+            .await?;
+        updated += 1;
+    }
+
+    tracing::info!("backfilled {updated} of {} images", db_images.len());
+
+    Ok(())
+}
+
+/// Generates renditions of images stored before they were kept, treating the
+/// stored file as the original.
+///
+/// Images that cannot be read or decoded within `upload_limits` are reported
+/// and skipped.
+pub async fn backfill_renditions(
+    db: &dyn Repository,
+    storage: &dyn Storage,
+    upload_limits: &UploadLimits,
+    rendition_rules: &RenditionRules,
+) -> sqlx::Result<()> {
+    let db_images = db.get_images_missing_renditions().await?;
+    let mut generated = 0;
+
+    for db_image in &db_images {
+        let key = original_key(&db_image.id, &db_image.extension);
+
+        let renditions = match storage.get(&key).await {
+            Ok(bytes) => render(&bytes, upload_limits, rendition_rules),
+            Err(error) => Err(error.into()),
+        };
+
+        let renditions = match renditions {
+            Ok(renditions) => renditions,
+            Err(error) => {
+                tracing::error!("skipping {key}: {error}");
+                continue;
+            }
+        };
+
+        if let Err(error) = put_renditions(storage, &db_image.id, &renditions).await {
+            tracing::error!("skipping {key}: {error}");
+            continue;
+        }
+
+        db.insert_renditions(&db_image.id, &renditions).await?;
+        generated += 1;
+    }
+
+    tracing::info!(
+        "generated renditions of {generated} of {} images",
+        db_images.len()
+    );
+
+    Ok(())
+}
+
+async fn put_renditions(
+    storage: &dyn Storage,
+    id: &str,
+    renditions: &[EncodedRendition],
+) -> io::Result<()> {
+    for rendition in renditions {
+        storage
+            .put(
+                &rendition_key(id, rendition.width, rendition.extension),
+                rendition.bytes.clone(),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Renders an original the way an upload is, without rotating or cropping.
+fn render(
+    bytes: &[u8],
+    upload_limits: &UploadLimits,
+    rendition_rules: &RenditionRules,
+) -> ImageResult<Vec<EncodedRendition>> {
+    let format = image::guess_format(bytes)?;
+
+    if is_animated(bytes, &upload_limits.animation_limits())? {
+        let (width, height) =
+            ImageReader::with_format(Cursor::new(bytes), format).into_dimensions()?;
+
+        return Ok(vec![EncodedRendition {
+            width,
+            height,
+            extension: format.extensions_str()[0],
+            bytes: strip_metadata(bytes, format)?,
+        }]);
+    }
+
+    let (mut image, orientation) = upload_limits.decode(bytes, format)?;
+    image.apply_orientation(orientation);
+
+    rendition_rules.render(&image)
+}
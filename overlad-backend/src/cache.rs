@@ -0,0 +1,210 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use axum::body::Bytes;
+use sha2::{Digest, Sha256};
+
+/// An encoded overlay ready to be sent to a client.
+#[derive(Clone)]
+pub struct RenderedOverlay {
+    pub content_type: &'static str,
+    pub body: Bytes,
+    pub etag: String,
+}
+
+impl RenderedOverlay {
+    pub fn new(content_type: &'static str, body: impl Into<Bytes>) -> Self {
+        let body = body.into();
+        let digest = Sha256::digest(&body);
+
+        Self {
+            content_type,
+            etag: format!("\"{}\"", hex::encode(&digest[..16])),
+            body,
+        }
+    }
+}
+
+/// An in-memory least recently used cache of rendered overlays.
+///
+/// Entries are evicted oldest first once either the entry count or the total
+/// size of the cached bodies goes over its limit. A limit of 0 disables the
+/// cache.
+pub struct OverlayCache {
+    max_entries: usize,
+    max_bytes: usize,
+    inner: Mutex<OverlayCacheInner>,
+}
+
+#[derive(Default)]
+struct OverlayCacheInner {
+    entries: HashMap<String, (RenderedOverlay, u64)>,
+    recency: BTreeMap<u64, String>,
+    total_bytes: usize,
+    tick: u64,
+}
+
+impl OverlayCache {
+    pub fn new(max_entries: usize, max_bytes: usize) -> Self {
+        Self {
+            max_entries,
+            max_bytes,
+            inner: Mutex::new(OverlayCacheInner::default()),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<RenderedOverlay> {
+        let mut guard = self.inner.lock().unwrap();
+        let inner = &mut *guard;
+
+        inner.tick += 1;
+        let tick = inner.tick;
+
+        let (rendered, last_used) = inner.entries.get_mut(key)?;
+        let rendered = rendered.clone();
+        let previous_use = std::mem::replace(last_used, tick);
+
+        inner.recency.remove(&previous_use);
+        inner.recency.insert(tick, key.to_owned());
+
+        Some(rendered)
+    }
+
+    pub fn insert(&self, key: String, rendered: RenderedOverlay) {
+        if self.max_entries == 0 || rendered.body.len() > self.max_bytes {
+            return;
+        }
+
+        let mut guard = self.inner.lock().unwrap();
+        let inner = &mut *guard;
+
+        inner.tick += 1;
+        let tick = inner.tick;
+
+        if let Some((previous, previous_use)) = inner.entries.remove(&key) {
+            inner.total_bytes -= previous.body.len();
+            inner.recency.remove(&previous_use);
+        }
+
+        inner.total_bytes += rendered.body.len();
+        inner.entries.insert(key.clone(), (rendered, tick));
+        inner.recency.insert(tick, key);
+
+        while inner.entries.len() > self.max_entries || inner.total_bytes > self.max_bytes {
+            let Some((_, oldest_key)) = inner.recency.pop_first() else {
+                break;
+            };
+
+            if let Some((evicted, _)) = inner.entries.remove(&oldest_key) {
+                inner.total_bytes -= evicted.body.len();
+            }
+        }
+    }
+
+    /// Drops every rendering whose key starts with `prefix`.
+    pub fn remove_prefix(&self, prefix: &str) {
+        let mut guard = self.inner.lock().unwrap();
+        let inner = &mut *guard;
+
+        let keys = inner
+            .entries
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect::<Vec<String>>();
+
+        for key in keys {
+            if let Some((removed, last_used)) = inner.entries.remove(&key) {
+                inner.total_bytes -= removed.body.len();
+                inner.recency.remove(&last_used);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rendered(len: usize) -> RenderedOverlay {
+        RenderedOverlay::new("image/webp", vec![0; len])
+    }
+
+    fn cached_keys(cache: &OverlayCache, keys: &[&str]) -> Vec<String> {
+        keys.iter()
+            .filter(|key| cache.get(key).is_some())
+            .map(|key| key.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn least_recently_used_entries_are_evicted_past_the_entry_limit() {
+        let cache = OverlayCache::new(2, 1000);
+
+        cache.insert(String::from("a"), rendered(1));
+        cache.insert(String::from("b"), rendered(1));
+        cache.get("a");
+        cache.insert(String::from("c"), rendered(1));
+
+        assert_eq!(cached_keys(&cache, &["a", "b", "c"]), ["a", "c"]);
+    }
+
+    #[test]
+    fn least_recently_used_entries_are_evicted_past_the_byte_limit() {
+        let cache = OverlayCache::new(10, 10);
+
+        cache.insert(String::from("a"), rendered(4));
+        cache.insert(String::from("b"), rendered(4));
+        cache.get("a");
+        cache.insert(String::from("c"), rendered(4));
+
+        assert_eq!(cached_keys(&cache, &["a", "b", "c"]), ["a", "c"]);
+
+        // Replacing an entry counts only its new size.
+        cache.insert(String::from("c"), rendered(6));
+        assert_eq!(cached_keys(&cache, &["a", "c"]), ["a", "c"]);
+    }
+
+    #[test]
+    fn overlays_over_the_byte_limit_are_not_cached() {
+        let cache = OverlayCache::new(10, 10);
+
+        cache.insert(String::from("a"), rendered(4));
+        cache.insert(String::from("b"), rendered(11));
+
+        assert_eq!(cached_keys(&cache, &["a", "b"]), ["a"]);
+    }
+
+    #[test]
+    fn no_entries_disables_the_cache() {
+        let cache = OverlayCache::new(0, 1000);
+
+        cache.insert(String::from("a"), rendered(1));
+
+        assert!(cache.get("a").is_none());
+    }
+
+    #[test]
+    fn entries_are_removed_by_prefix() {
+        let cache = OverlayCache::new(10, 1000);
+
+        for key in ["abc.source?", "abc.png?text=a", "abcd.source?"] {
+            cache.insert(String::from(key), rendered(1));
+        }
+        cache.remove_prefix("abc.");
+
+        assert_eq!(
+            cached_keys(&cache, &["abc.source?", "abc.png?text=a", "abcd.source?"]),
+            ["abcd.source?"]
+        );
+    }
+
+    #[test]
+    fn etags_follow_the_body() {
+        assert_eq!(rendered(1).etag, rendered(1).etag);
+        assert_ne!(rendered(1).etag, rendered(2).etag);
+        assert!(rendered(1).etag.starts_with('"') && rendered(1).etag.ends_with('"'));
+    }
+}
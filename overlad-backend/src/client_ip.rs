@@ -0,0 +1,57 @@
+use std::{convert::Infallible, net::IpAddr};
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, connect_info::Connected},
+    http::request::Parts,
+    serve::IncomingStream,
+};
+use tokio::net::{TcpListener, UnixListener};
+
+use crate::AppState;
+
+/// The address of the connected peer, which Unix domain sockets do not have.
+#[derive(Debug, Clone, Copy)]
+pub struct PeerAddr(pub Option<IpAddr>);
+
+impl Connected<IncomingStream<'_, TcpListener>> for PeerAddr {
+    fn connect_info(stream: IncomingStream<'_, TcpListener>) -> Self {
+        Self(Some(stream.remote_addr().ip()))
+    }
+}
+
+impl Connected<IncomingStream<'_, UnixListener>> for PeerAddr {
+    fn connect_info(_stream: IncomingStream<'_, UnixListener>) -> Self {
+        Self(None)
+    }
+}
+
+/// The IP address of the client that made the request.
+///
+/// When a forwarded header is configured it is trusted over the peer address,
+/// taking its last entry since that is the one added by our own proxy. Without
+/// one, requests over a Unix domain socket have no client IP.
+pub struct ClientIp(pub Option<IpAddr>);
+
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let forwarded_ip = state
+            .forwarded_header
+            .as_ref()
+            .and_then(|forwarded_header| parts.headers.get(forwarded_header))
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|ip| ip.trim().parse().ok());
+
+        let peer_ip = parts
+            .extensions
+            .get::<ConnectInfo<PeerAddr>>()
+            .and_then(|ConnectInfo(PeerAddr(peer_ip))| *peer_ip);
+
+        Ok(Self(forwarded_ip.or(peer_ip)))
+    }
+}
@@ -0,0 +1,218 @@
+use std::{collections::HashSet, io, path::PathBuf};
+
+use clap::{
+    Arg, ArgMatches, Command, Id, Parser, error::ErrorKind, parser::ValueSource, value_parser,
+};
+use toml::{Table, Value};
+
+/// Config file read when none is given, which may be missing.
+const DEFAULT_CONFIG_PATH: &str = "overlad.toml";
+
+/// The options of a parsed command line and where each value came from.
+///
+/// Values are layered with the command line over the environment, the
+/// environment over the config file and the config file over the built-in
+/// defaults. The config file is TOML with one key per long option, in
+/// snake_case or kebab-case, and arrays for comma separated options.
+pub struct Config {
+    command: Command,
+    /// Ids of the options in the order they are declared, which setting
+    /// defaults from the config file changes.
+    ids: Vec<Id>,
+    matches: ArgMatches,
+    path: Option<PathBuf>,
+    file_ids: HashSet<String>,
+}
+
+impl Config {
+    /// Parses the command line of `T`, exiting with an error when it or the
+    /// config file is invalid.
+    pub fn parse<T: Parser>() -> (T, Self) {
+        let mut command = T::command()
+            .arg(
+                Arg::new("config")
+                    .long("config")
+                    .env("OVERLAD_CONFIG")
+                    .value_parser(value_parser!(PathBuf))
+                    .default_value(DEFAULT_CONFIG_PATH)
+                    .help("TOML file of options, ignored when the default one is missing"),
+            )
+            // Options may be given before or after a subcommand, since
+            // `config check`, `backfill` and `migrate` read them too.
+            .mut_args(|arg| arg.global(true));
+
+        // The config file has to be found before the rest of the command line
+        // can be parsed with its values as defaults.
+        let early_matches = command.clone().ignore_errors(true).get_matches();
+        let explicit_path = early_matches
+            .value_source("config")
+            .is_some_and(|source| source != ValueSource::DefaultValue);
+        // Defaults are missing when parsing stopped early at a bad value.
+        let path = early_matches
+            .get_one::<PathBuf>("config")
+            .cloned()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH));
+
+        let table = match std::fs::read_to_string(&path) {
+            Ok(contents) => Some(contents.parse::<Table>().unwrap_or_else(|error| {
+                command
+                    .error(
+                        ErrorKind::Io,
+                        format!("bad config file {}: {error}", path.display()),
+                    )
+                    .exit()
+            })),
+            Err(error) if error.kind() == io::ErrorKind::NotFound && !explicit_path => None,
+            Err(error) => command
+                .error(
+                    ErrorKind::Io,
+                    format!("could not read config file {}: {error}", path.display()),
+                )
+                .exit(),
+        };
+
+        let ids = command
+            .get_arguments()
+            .map(|arg| arg.get_id().clone())
+            .collect::<Vec<_>>();
+        let mut file_ids = HashSet::new();
+
+        for (key, value) in table.iter().flatten() {
+            let id = key.replace('-', "_");
+
+            if id == "config"
+                || !command
+                    .get_arguments()
+                    .any(|arg| arg.get_id() == id.as_str())
+            {
+                command
+                    .error(
+                        ErrorKind::UnknownArgument,
+                        format!("unknown option `{key}` in config file {}", path.display()),
+                    )
+                    .exit();
+            }
+
+            let Some(default_value) = toml_to_arg(value) else {
+                command
+                    .error(
+                        ErrorKind::InvalidValue,
+                        format!(
+                            "option `{key}` in config file {} must be a string, number, boolean or array of those",
+                            path.display()
+                        ),
+                    )
+                    .exit();
+            };
+
+            // Defaults have to outlive the command, which lives until exit
+            // anyway.
+            let default_value: &'static str = default_value.leak();
+            command = command.mut_arg(&id, |arg| arg.default_value(default_value));
+            file_ids.insert(id);
+        }
+
+        let matches = command.clone().get_matches();
+        let parsed = T::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+
+        let config = Self {
+            command,
+            ids,
+            matches,
+            path: table.is_some().then_some(path),
+            file_ids,
+        };
+
+        (parsed, config)
+    }
+
+    /// Exits with a value validation error about the configuration.
+    pub fn exit_with_error(&self, message: impl std::fmt::Display) -> ! {
+        self.command
+            .clone()
+            .error(ErrorKind::ValueValidation, message)
+            .exit()
+    }
+
+    /// Prints the effective configuration as a config file, noting where each
+    /// value came from. Values whose environment variable is hidden from the
+    /// help, such as secrets, are masked.
+    pub fn print(&self) {
+        match &self.path {
+            Some(path) => println!("# config file: {}", path.display()),
+            None => println!("# config file: none"),
+        }
+
+        for id in &self.ids {
+            let id = id.as_str();
+            let arg = self
+                .command
+                .get_arguments()
+                .find(|arg| arg.get_id() == id)
+                .expect("ids are of the command's arguments");
+
+            if matches!(id, "help" | "version" | "config") {
+                continue;
+            }
+
+            let source = match self.matches.value_source(id) {
+                Some(ValueSource::CommandLine) => "command line",
+                Some(ValueSource::EnvVariable) => "environment",
+                Some(ValueSource::DefaultValue) if self.file_ids.contains(id) => "config file",
+                Some(_) => "default",
+                None => {
+                    println!("# {id} is not set");
+                    continue;
+                }
+            };
+
+            let values = self
+                .matches
+                .get_raw(id)
+                .into_iter()
+                .flatten()
+                .map(|value| toml_value(&value.to_string_lossy()))
+                .collect::<Vec<_>>();
+
+            let value = if arg.is_hide_env_values_set() {
+                String::from("\"<hidden>\"")
+            } else if arg.get_value_delimiter().is_some() {
+                format!("[{}]", values.join(", "))
+            } else {
+                values.join(", ")
+            };
+
+            println!("{id} = {value} # {source}");
+        }
+    }
+}
+
+/// Converts a config file value to how it would be written on the command
+/// line, or `None` for tables and dates.
+fn toml_to_arg(value: &Value) -> Option<String> {
+    match value {
+        Value::String(string) => Some(string.clone()),
+        Value::Integer(integer) => Some(integer.to_string()),
+        Value::Float(float) => Some(float.to_string()),
+        Value::Boolean(boolean) => Some(boolean.to_string()),
+        Value::Array(values) => values
+            .iter()
+            .map(|value| match value {
+                Value::Array(_) => None,
+                value => toml_to_arg(value),
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(|values| values.join(",")),
+        Value::Table(_) | Value::Datetime(_) => None,
+    }
+}
+
+/// Writes a command line value as TOML, unquoted when it is a number or
+/// boolean.
+fn toml_value(value: &str) -> String {
+    if value.parse::<i64>().is_ok() || value.parse::<bool>().is_ok() {
+        value.to_owned()
+    } else {
+        Value::String(value.to_owned()).to_string()
+    }
+}
@@ -0,0 +1,69 @@
+use axum::http::{HeaderName, HeaderValue, Method, Uri};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+/// Which cross-origin requests browsers may make to the API.
+///
+/// Overlays are not covered, since they are meant to be embedded anywhere and
+/// always allow any origin.
+#[derive(Debug, Clone)]
+pub struct CorsRules {
+    /// Allowed origins, where `*` allows any.
+    pub origins: Vec<HeaderValue>,
+    pub methods: Vec<Method>,
+    pub headers: Vec<HeaderName>,
+}
+
+impl CorsRules {
+    pub fn layer(&self) -> CorsLayer {
+        let allow_origin = if self.origins.iter().any(|origin| origin == "*") {
+            AllowOrigin::any()
+        } else {
+            AllowOrigin::list(self.origins.iter().cloned())
+        };
+
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods(self.methods.clone())
+            .allow_headers(self.headers.clone())
+    }
+}
+
+/// Lets any origin fetch overlays, without credentials.
+pub fn public_layer() -> CorsLayer {
+    CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods([Method::GET, Method::HEAD])
+}
+
+/// Parses an origin browsers may call the API from, such as
+/// `https://overlad.example`, or `*` for any.
+pub fn parse_origin(origin: &str) -> Result<HeaderValue, String> {
+    if origin == "*" {
+        return Ok(HeaderValue::from_static("*"));
+    }
+
+    let uri = origin
+        .parse::<Uri>()
+        .map_err(|error| format!("bad origin {origin}: {error}"))?;
+
+    if uri.scheme().is_none()
+        || uri.authority().is_none()
+        || uri.path_and_query().is_some_and(|path| path != "/")
+        || origin.ends_with('/')
+    {
+        return Err(format!(
+            "bad origin {origin}: expected a scheme and host, such as https://overlad.example"
+        ));
+    }
+
+    HeaderValue::from_str(origin).map_err(|error| format!("bad origin {origin}: {error}"))
+}
+
+/// Parses an HTTP method case-insensitively, since methods are matched in
+/// upper case.
+pub fn parse_method(method: &str) -> Result<Method, String> {
+    method
+        .to_ascii_uppercase()
+        .parse()
+        .map_err(|_| format!("bad method {method}"))
+}
@@ -1,69 +1,119 @@
+use std::io::Cursor;
+
+use chrono::NaiveDateTime;
+use image::{ImageReader, ImageResult};
 use overlad_api::{Image, User};
-use sqlx::{SqlitePool, query_as};
 
-use crate::{db::user::DbUser, util::to_row_not_found};
+use crate::{
+    db::{Repository, user::DbUser},
+    util::to_row_not_found,
+};
 
+#[derive(sqlx::FromRow)]
 pub struct DbImage {
     pub id: String,
     pub user_id: i64,
     pub extension: String,
+    pub width: i64,
+    pub height: i64,
+    pub size_bytes: i64,
+    pub created_at: NaiveDateTime,
+    pub original_filename: Option<String>,
 }
 
-impl DbImage {
-    pub async fn insert(
-        pool: &SqlitePool,
-        id: &str,
-        user_id: i64,
-        extension: &str,
-    ) -> sqlx::Result<DbImage> {
-        query_as!(
-            Self,
-            "INSERT INTO images VALUES (?, ?, ?) RETURNING *",
-            id,
-            user_id,
-            extension
-        )
-        .fetch_one(pool)
-        .await
-    }
+/// An image row joined with the username of its uploader.
+#[derive(sqlx::FromRow)]
+pub struct DbImageWithUser {
+    pub id: String,
+    pub user_id: i64,
+    pub extension: String,
+    pub width: i64,
+    pub height: i64,
+    pub size_bytes: i64,
+    pub created_at: NaiveDateTime,
+    pub original_filename: Option<String>,
+    pub username: String,
+}
 
-    pub async fn get_all(pool: &SqlitePool) -> sqlx::Result<Vec<Self>> {
-        query_as!(Self, "SELECT * FROM images")
-            .fetch_all(pool)
-            .await
-    }
+/// Where a page of images starts, exclusive.
+pub struct ImageCursor {
+    pub created_at: NaiveDateTime,
+    pub id: String,
+}
 
-    pub async fn get_by_id(pool: &SqlitePool, id: impl AsRef<str>) -> sqlx::Result<Option<Self>> {
-        let id_ref = id.as_ref();
+/// What is known about a stored image file.
+pub struct ImageMetadata {
+    pub width: u32,
+    pub height: u32,
+    pub size_bytes: u64,
+}
 
-        query_as!(Self, "SELECT * FROM images WHERE id = ?", id_ref)
-            .fetch_optional(pool)
-            .await
-    }
+impl ImageMetadata {
+    /// Reads the dimensions and size of a stored image file.
+    pub fn from_bytes(bytes: &[u8]) -> ImageResult<Self> {
+        let (width, height) = ImageReader::new(Cursor::new(bytes))
+            .with_guessed_format()?
+            .into_dimensions()?;
+        let size_bytes = bytes.len() as u64;
 
-    pub async fn get_by_user_id(pool: &SqlitePool, user_id: i64) -> sqlx::Result<Vec<Self>> {
-        query_as!(Self, "SELECT * FROM images WHERE user_id = ?", user_id,)
-            .fetch_all(pool)
-            .await
+        Ok(Self {
+            width,
+            height,
+            size_bytes,
+        })
     }
+}
 
-    pub async fn get_db_user(&self, pool: &SqlitePool) -> sqlx::Result<DbUser> {
-        DbUser::get_by_id(pool, self.user_id)
+impl DbImage {
+    pub async fn get_db_user(&self, db: &dyn Repository) -> sqlx::Result<DbUser> {
+        db.get_user_by_id(self.user_id)
             .await
             .and_then(to_row_not_found)
     }
 
-    pub async fn get_user(&self, pool: &SqlitePool) -> sqlx::Result<User> {
-        self.get_db_user(pool).await.map(User::from)
+    pub async fn get_user(&self, db: &dyn Repository) -> sqlx::Result<User> {
+        self.get_db_user(db).await.map(User::from)
     }
 
-    pub async fn into_image(self, pool: &SqlitePool) -> sqlx::Result<Image> {
-        let user = self.get_user(pool).await?;
+    pub async fn into_image(self, db: &dyn Repository) -> sqlx::Result<Image> {
+        let user = self.get_user(db).await?;
 
         Ok(Image {
             id: self.id,
             user,
             extension: self.extension,
+            width: self.width as u32,
+            height: self.height as u32,
+            size_bytes: self.size_bytes as u64,
+            created_at: self.created_at.and_utc(),
+            original_filename: self.original_filename,
         })
     }
 }
+
+impl DbImageWithUser {
+    pub fn cursor(&self) -> ImageCursor {
+        ImageCursor {
+            created_at: self.created_at,
+            id: self.id.clone(),
+        }
+    }
+}
+
+impl From<DbImageWithUser> for Image {
+    fn from(value: DbImageWithUser) -> Self {
+        Image {
+            id: value.id,
+            user: User {
+                id: value.user_id,
+                username: value.username,
+            },
+            extension: value.extension,
+            width: value.width as u32,
+            height: value.height as u32,
+            size_bytes: value.size_bytes as u64,
+            created_at: value.created_at.and_utc(),
+            original_filename: value.original_filename,
+        }
+    }
+}
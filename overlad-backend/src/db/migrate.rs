@@ -0,0 +1,75 @@
+use std::collections::HashSet;
+
+use sqlx::{
+    Database, Pool,
+    migrate::{Migrate, MigrateError, Migrator},
+};
+
+/// Whether a migration embedded in the binary has been applied.
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+pub(super) async fn status<DB>(
+    pool: &Pool<DB>,
+    migrator: &Migrator,
+) -> Result<Vec<MigrationStatus>, MigrateError>
+where
+    DB: Database,
+    DB::Connection: Migrate,
+{
+    let mut connection = pool.acquire().await?;
+    connection.ensure_migrations_table().await?;
+
+    let applied = connection
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|applied_migration| applied_migration.version)
+        .collect::<HashSet<_>>();
+
+    Ok(migrator
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| MigrationStatus {
+            version: migration.version,
+            description: migration.description.to_string(),
+            applied: applied.contains(&migration.version),
+        })
+        .collect())
+}
+
+/// Reverts the latest applied migration and returns its version, or `None`
+/// when none is applied.
+pub(super) async fn undo_latest<DB>(
+    pool: &Pool<DB>,
+    migrator: &Migrator,
+) -> Result<Option<i64>, MigrateError>
+where
+    DB: Database,
+    DB::Connection: Migrate,
+{
+    let mut connection = pool.acquire().await?;
+    connection.ensure_migrations_table().await?;
+
+    let applied = connection.list_applied_migrations().await?;
+    drop(connection);
+
+    let Some(latest) = applied.last() else {
+        return Ok(None);
+    };
+
+    // Undoing reverts everything after the target, so target the migration
+    // before the latest one.
+    let target = applied
+        .iter()
+        .rev()
+        .nth(1)
+        .map_or(0, |applied_migration| applied_migration.version);
+
+    migrator.undo(pool, target).await?;
+
+    Ok(Some(latest.version))
+}
@@ -1,2 +1,147 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use overlad_api::ImageSort;
+use sqlx::migrate::MigrateError;
+
+use crate::{
+    db::{
+        image::{DbImage, DbImageWithUser, ImageCursor, ImageMetadata},
+        migrate::MigrationStatus,
+        postgres::PostgresRepository,
+        rendition::DbRendition,
+        sqlite::SqliteRepository,
+        user::DbUser,
+    },
+    rendition::EncodedRendition,
+};
+
 pub mod image;
+pub mod migrate;
+pub mod postgres;
+pub mod rendition;
+pub mod sqlite;
 pub mod user;
+
+/// Every database operation of the backend, implemented for SQLite and
+/// Postgres.
+#[async_trait]
+pub trait Repository: Send + Sync {
+    /// Applies the embedded migrations that have not been applied yet.
+    async fn migrate_up(&self) -> Result<(), MigrateError>;
+
+    /// Reverts the latest applied migration and returns its version, or
+    /// `None` when none is applied.
+    async fn migrate_down(&self) -> Result<Option<i64>, MigrateError>;
+
+    /// Every embedded migration, oldest first.
+    async fn migration_status(&self) -> Result<Vec<MigrationStatus>, MigrateError>;
+
+    async fn insert_user(&self, username: &str, passhash: &str) -> sqlx::Result<DbUser>;
+
+    async fn get_user_by_id(&self, id: i64) -> sqlx::Result<Option<DbUser>>;
+
+    async fn get_user_by_username(&self, username: &str) -> sqlx::Result<Option<DbUser>>;
+
+    /// Changes the password and revokes every refresh token of the user at
+    /// `now`, all or nothing, so a stolen session ends with the change.
+    async fn update_password(
+        &self,
+        user_id: i64,
+        passhash: &str,
+        now: NaiveDateTime,
+    ) -> sqlx::Result<()>;
+
+    /// Inserts an image along with its renditions, all or nothing.
+    async fn insert_image(
+        &self,
+        id: &str,
+        user_id: i64,
+        extension: &str,
+        metadata: &ImageMetadata,
+        original_filename: Option<&str>,
+        renditions: &[EncodedRendition],
+    ) -> sqlx::Result<DbImage>;
+
+    async fn get_image_by_id(&self, id: &str) -> sqlx::Result<Option<DbImage>>;
+
+    /// Lists up to `limit` images after `cursor` in `sort` order, optionally
+    /// only those uploaded by `user_id`.
+    ///
+    /// Images are ordered by upload time with the id breaking ties, so a cursor
+    /// always points at a unique position.
+    async fn get_image_page(
+        &self,
+        user_id: Option<i64>,
+        sort: ImageSort,
+        cursor: Option<&ImageCursor>,
+        limit: u32,
+    ) -> sqlx::Result<Vec<DbImageWithUser>>;
+
+    /// Deletes an image along with its renditions.
+    async fn delete_image(&self, id: &str) -> sqlx::Result<()>;
+
+    /// Images stored before their metadata was recorded, which still have the
+    /// placeholder size of 0.
+    async fn get_images_missing_metadata(&self) -> sqlx::Result<Vec<DbImage>>;
+
+    /// Images stored before renditions were kept, which have none.
+    async fn get_images_missing_renditions(&self) -> sqlx::Result<Vec<DbImage>>;
+
+    async fn update_image_metadata(
+        &self,
+        id: &str,
+        metadata: &ImageMetadata,
+        created_at: NaiveDateTime,
+    ) -> sqlx::Result<()>;
+
+    /// Inserts the renditions of an existing image, all or nothing.
+    async fn insert_renditions(
+        &self,
+        image_id: &str,
+        renditions: &[EncodedRendition],
+    ) -> sqlx::Result<()>;
+
+    /// The renditions of an image, smallest first.
+    async fn get_renditions(&self, image_id: &str) -> sqlx::Result<Vec<DbRendition>>;
+
+    async fn insert_refresh_token(
+        &self,
+        token_hash: &str,
+        user_id: i64,
+        created_at: NaiveDateTime,
+        expires_at: NaiveDateTime,
+    ) -> sqlx::Result<()>;
+
+    /// Revokes the token if it is still valid at `now` and returns the id of
+    /// the user it belongs to.
+    ///
+    /// Checking and revoking happen in one statement, so a refresh token can
+    /// only ever be exchanged once.
+    async fn consume_refresh_token(
+        &self,
+        token_hash: &str,
+        now: NaiveDateTime,
+    ) -> sqlx::Result<Option<i64>>;
+
+    async fn revoke_refresh_token(&self, token_hash: &str, now: NaiveDateTime) -> sqlx::Result<()>;
+
+    /// Revokes every refresh token of the user that is not revoked yet.
+    async fn revoke_user_refresh_tokens(
+        &self,
+        user_id: i64,
+        now: NaiveDateTime,
+    ) -> sqlx::Result<()>;
+}
+
+/// Connects to the database at `url`, using Postgres for `postgres://` and
+/// `postgresql://` URLs and SQLite otherwise. A missing SQLite database file
+/// is created.
+pub async fn connect(url: &str) -> sqlx::Result<Arc<dyn Repository>> {
+    if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+        Ok(Arc::new(PostgresRepository::connect(url).await?))
+    } else {
+        Ok(Arc::new(SqliteRepository::connect(url).await?))
+    }
+}
@@ -0,0 +1,323 @@
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use overlad_api::ImageSort;
+use sqlx::{
+    PgConnection, PgPool, Postgres, Transaction,
+    migrate::{MigrateError, Migrator},
+    query, query_as, query_scalar,
+};
+
+use crate::{
+    db::{
+        Repository,
+        image::{DbImage, DbImageWithUser, ImageCursor, ImageMetadata},
+        migrate::{self, MigrationStatus},
+        rendition::DbRendition,
+        user::DbUser,
+    },
+    rendition::EncodedRendition,
+};
+
+static MIGRATOR: Migrator = sqlx::migrate!("../migrations/postgres");
+
+const IMAGE_COLUMNS: &str =
+    "id, user_id, extension, width, height, size_bytes, created_at, original_filename";
+
+/// A [`Repository`] backed by Postgres.
+///
+/// The query macros only check against the database of `DATABASE_URL`, which
+/// is SQLite at build time, so these queries are checked at runtime instead.
+pub struct PostgresRepository {
+    pool: PgPool,
+}
+
+impl PostgresRepository {
+    pub async fn connect(url: &str) -> sqlx::Result<Self> {
+        Ok(Self {
+            pool: PgPool::connect(url).await?,
+        })
+    }
+}
+
+async fn insert_renditions(
+    transaction: &mut Transaction<'_, Postgres>,
+    image_id: &str,
+    renditions: &[EncodedRendition],
+) -> sqlx::Result<()> {
+    for rendition in renditions {
+        query(
+            "INSERT INTO image_renditions (image_id, width, height, extension, size_bytes) VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(image_id)
+        .bind(i64::from(rendition.width))
+        .bind(i64::from(rendition.height))
+        .bind(rendition.extension)
+        .bind(rendition.bytes.len() as i64)
+        .execute(&mut **transaction)
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn revoke_user_refresh_tokens(
+    connection: &mut PgConnection,
+    user_id: i64,
+    now: NaiveDateTime,
+) -> sqlx::Result<()> {
+    query("UPDATE refresh_tokens SET revoked_at = $1 WHERE user_id = $2 AND revoked_at IS NULL")
+        .bind(now)
+        .bind(user_id)
+        .execute(connection)
+        .await?;
+
+    Ok(())
+}
+
+#[async_trait]
+impl Repository for PostgresRepository {
+    async fn migrate_up(&self) -> Result<(), MigrateError> {
+        MIGRATOR.run(&self.pool).await
+    }
+
+    async fn migrate_down(&self) -> Result<Option<i64>, MigrateError> {
+        migrate::undo_latest(&self.pool, &MIGRATOR).await
+    }
+
+    async fn migration_status(&self) -> Result<Vec<MigrationStatus>, MigrateError> {
+        migrate::status(&self.pool, &MIGRATOR).await
+    }
+
+    async fn insert_user(&self, username: &str, passhash: &str) -> sqlx::Result<DbUser> {
+        query_as(
+            "INSERT INTO users (username, passhash) VALUES ($1, $2) RETURNING id, username, passhash",
+        )
+        .bind(username)
+        .bind(passhash)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn get_user_by_id(&self, id: i64) -> sqlx::Result<Option<DbUser>> {
+        query_as("SELECT id, username, passhash FROM users WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn get_user_by_username(&self, username: &str) -> sqlx::Result<Option<DbUser>> {
+        query_as("SELECT id, username, passhash FROM users WHERE username = $1")
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn update_password(
+        &self,
+        user_id: i64,
+        passhash: &str,
+        now: NaiveDateTime,
+    ) -> sqlx::Result<()> {
+        let mut transaction = self.pool.begin().await?;
+
+        query("UPDATE users SET passhash = $1 WHERE id = $2")
+            .bind(passhash)
+            .bind(user_id)
+            .execute(&mut *transaction)
+            .await?;
+
+        revoke_user_refresh_tokens(&mut transaction, user_id, now).await?;
+
+        transaction.commit().await
+    }
+
+    async fn insert_image(
+        &self,
+        id: &str,
+        user_id: i64,
+        extension: &str,
+        metadata: &ImageMetadata,
+        original_filename: Option<&str>,
+        renditions: &[EncodedRendition],
+    ) -> sqlx::Result<DbImage> {
+        let created_at = Utc::now().naive_utc();
+
+        let mut transaction = self.pool.begin().await?;
+
+        let db_image = query_as(&format!(
+            "INSERT INTO images ({IMAGE_COLUMNS}) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING {IMAGE_COLUMNS}"
+        ))
+        .bind(id)
+        .bind(user_id)
+        .bind(extension)
+        .bind(i64::from(metadata.width))
+        .bind(i64::from(metadata.height))
+        .bind(metadata.size_bytes as i64)
+        .bind(created_at)
+        .bind(original_filename)
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        insert_renditions(&mut transaction, id, renditions).await?;
+
+        transaction.commit().await?;
+
+        Ok(db_image)
+    }
+
+    async fn get_image_by_id(&self, id: &str) -> sqlx::Result<Option<DbImage>> {
+        query_as(&format!("SELECT {IMAGE_COLUMNS} FROM images WHERE id = $1"))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn get_image_page(
+        &self,
+        user_id: Option<i64>,
+        sort: ImageSort,
+        cursor: Option<&ImageCursor>,
+        limit: u32,
+    ) -> sqlx::Result<Vec<DbImageWithUser>> {
+        let (comparison, direction) = match sort {
+            ImageSort::Newest => ("<", "DESC"),
+            ImageSort::Oldest => (">", "ASC"),
+        };
+
+        query_as(&format!(
+            "SELECT images.*, users.username
+            FROM images JOIN users ON users.id = images.user_id
+            WHERE ($1::BIGINT IS NULL OR images.user_id = $1)
+            AND ($2::TIMESTAMP IS NULL OR (images.created_at, images.id) {comparison} ($2, $3))
+            ORDER BY images.created_at {direction}, images.id {direction}
+            LIMIT $4"
+        ))
+        .bind(user_id)
+        .bind(cursor.map(|cursor| cursor.created_at))
+        .bind(cursor.map(|cursor| cursor.id.as_str()))
+        .bind(i64::from(limit))
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn delete_image(&self, id: &str) -> sqlx::Result<()> {
+        // Renditions are deleted along with the image.
+        query("DELETE FROM images WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_images_missing_metadata(&self) -> sqlx::Result<Vec<DbImage>> {
+        query_as(&format!(
+            "SELECT {IMAGE_COLUMNS} FROM images WHERE size_bytes = 0"
+        ))
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn get_images_missing_renditions(&self) -> sqlx::Result<Vec<DbImage>> {
+        query_as(&format!(
+            "SELECT {IMAGE_COLUMNS} FROM images WHERE id NOT IN (SELECT image_id FROM image_renditions)"
+        ))
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn update_image_metadata(
+        &self,
+        id: &str,
+        metadata: &ImageMetadata,
+        created_at: NaiveDateTime,
+    ) -> sqlx::Result<()> {
+        query(
+            "UPDATE images SET width = $1, height = $2, size_bytes = $3, created_at = $4 WHERE id = $5",
+        )
+        .bind(i64::from(metadata.width))
+        .bind(i64::from(metadata.height))
+        .bind(metadata.size_bytes as i64)
+        .bind(created_at)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn insert_renditions(
+        &self,
+        image_id: &str,
+        renditions: &[EncodedRendition],
+    ) -> sqlx::Result<()> {
+        let mut transaction = self.pool.begin().await?;
+
+        insert_renditions(&mut transaction, image_id, renditions).await?;
+
+        transaction.commit().await
+    }
+
+    async fn get_renditions(&self, image_id: &str) -> sqlx::Result<Vec<DbRendition>> {
+        query_as(
+            "SELECT image_id, width, height, extension FROM image_renditions WHERE image_id = $1 ORDER BY width",
+        )
+        .bind(image_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn insert_refresh_token(
+        &self,
+        token_hash: &str,
+        user_id: i64,
+        created_at: NaiveDateTime,
+        expires_at: NaiveDateTime,
+    ) -> sqlx::Result<()> {
+        query(
+            "INSERT INTO refresh_tokens (token_hash, user_id, created_at, expires_at) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(token_hash)
+        .bind(user_id)
+        .bind(created_at)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn consume_refresh_token(
+        &self,
+        token_hash: &str,
+        now: NaiveDateTime,
+    ) -> sqlx::Result<Option<i64>> {
+        query_scalar(
+            "UPDATE refresh_tokens SET revoked_at = $1 WHERE token_hash = $2 AND revoked_at IS NULL AND expires_at > $1 RETURNING user_id",
+        )
+        .bind(now)
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn revoke_refresh_token(&self, token_hash: &str, now: NaiveDateTime) -> sqlx::Result<()> {
+        query(
+            "UPDATE refresh_tokens SET revoked_at = $1 WHERE token_hash = $2 AND revoked_at IS NULL",
+        )
+        .bind(now)
+        .bind(token_hash)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn revoke_user_refresh_tokens(
+        &self,
+        user_id: i64,
+        now: NaiveDateTime,
+    ) -> sqlx::Result<()> {
+        revoke_user_refresh_tokens(&mut *self.pool.acquire().await?, user_id, now).await
+    }
+}
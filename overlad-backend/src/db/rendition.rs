@@ -0,0 +1,16 @@
+use crate::rendition::rendition_key;
+
+/// A stored rendition of an image. Rows are deleted along with their image.
+#[derive(sqlx::FromRow)]
+pub struct DbRendition {
+    pub image_id: String,
+    pub width: i64,
+    pub height: i64,
+    pub extension: String,
+}
+
+impl DbRendition {
+    pub fn key(&self) -> String {
+        rendition_key(&self.image_id, self.width as u32, &self.extension)
+    }
+}
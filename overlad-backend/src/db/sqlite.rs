@@ -0,0 +1,349 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use overlad_api::ImageSort;
+use sqlx::{
+    Sqlite, SqliteConnection, SqlitePool, Transaction,
+    migrate::{MigrateError, Migrator},
+    query, query_as, query_scalar,
+    sqlite::SqliteConnectOptions,
+};
+
+use crate::{
+    db::{
+        Repository,
+        image::{DbImage, DbImageWithUser, ImageCursor, ImageMetadata},
+        migrate::{self, MigrationStatus},
+        rendition::DbRendition,
+        user::DbUser,
+    },
+    rendition::EncodedRendition,
+};
+
+static MIGRATOR: Migrator = sqlx::migrate!("../migrations/sqlite");
+
+/// A [`Repository`] backed by SQLite, with queries checked at compile time.
+pub struct SqliteRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteRepository {
+    pub async fn connect(url: &str) -> sqlx::Result<Self> {
+        let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+
+        Ok(Self {
+            pool: SqlitePool::connect_with(options).await?,
+        })
+    }
+}
+
+async fn insert_renditions(
+    transaction: &mut Transaction<'_, Sqlite>,
+    image_id: &str,
+    renditions: &[EncodedRendition],
+) -> sqlx::Result<()> {
+    for rendition in renditions {
+        let size_bytes = rendition.bytes.len() as i64;
+
+        query!(
+            "INSERT INTO image_renditions VALUES (?, ?, ?, ?, ?)",
+            image_id,
+            rendition.width,
+            rendition.height,
+            rendition.extension,
+            size_bytes,
+        )
+        .execute(&mut **transaction)
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn revoke_user_refresh_tokens(
+    connection: &mut SqliteConnection,
+    user_id: i64,
+    now: NaiveDateTime,
+) -> sqlx::Result<()> {
+    query!(
+        "UPDATE refresh_tokens SET revoked_at = ? WHERE user_id = ? AND revoked_at IS NULL",
+        now,
+        user_id,
+    )
+    .execute(connection)
+    .await?;
+
+    Ok(())
+}
+
+#[async_trait]
+impl Repository for SqliteRepository {
+    async fn migrate_up(&self) -> Result<(), MigrateError> {
+        MIGRATOR.run(&self.pool).await
+    }
+
+    async fn migrate_down(&self) -> Result<Option<i64>, MigrateError> {
+        migrate::undo_latest(&self.pool, &MIGRATOR).await
+    }
+
+    async fn migration_status(&self) -> Result<Vec<MigrationStatus>, MigrateError> {
+        migrate::status(&self.pool, &MIGRATOR).await
+    }
+
+    async fn insert_user(&self, username: &str, passhash: &str) -> sqlx::Result<DbUser> {
+        query_as!(
+            DbUser,
+            "INSERT INTO users (username, passhash) VALUES (?, ?) RETURNING *",
+            username,
+            passhash,
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn get_user_by_id(&self, id: i64) -> sqlx::Result<Option<DbUser>> {
+        query_as!(DbUser, "SELECT * FROM users WHERE id = ?", id,)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn get_user_by_username(&self, username: &str) -> sqlx::Result<Option<DbUser>> {
+        query_as!(DbUser, "SELECT * FROM users WHERE username = ?", username,)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn update_password(
+        &self,
+        user_id: i64,
+        passhash: &str,
+        now: NaiveDateTime,
+    ) -> sqlx::Result<()> {
+        let mut transaction = self.pool.begin().await?;
+
+        query!(
+            "UPDATE users SET passhash = ? WHERE id = ?",
+            passhash,
+            user_id,
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        revoke_user_refresh_tokens(&mut transaction, user_id, now).await?;
+
+        transaction.commit().await
+    }
+
+    async fn insert_image(
+        &self,
+        id: &str,
+        user_id: i64,
+        extension: &str,
+        metadata: &ImageMetadata,
+        original_filename: Option<&str>,
+        renditions: &[EncodedRendition],
+    ) -> sqlx::Result<DbImage> {
+        let size_bytes = metadata.size_bytes as i64;
+        let created_at = Utc::now().naive_utc();
+
+        let mut transaction = self.pool.begin().await?;
+
+        let db_image = query_as!(
+            DbImage,
+            "INSERT INTO images VALUES (?, ?, ?, ?, ?, ?, ?, ?) RETURNING *",
+            id,
+            user_id,
+            extension,
+            metadata.width,
+            metadata.height,
+            size_bytes,
+            created_at,
+            original_filename,
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        insert_renditions(&mut transaction, id, renditions).await?;
+
+        transaction.commit().await?;
+
+        Ok(db_image)
+    }
+
+    async fn get_image_by_id(&self, id: &str) -> sqlx::Result<Option<DbImage>> {
+        query_as!(DbImage, "SELECT * FROM images WHERE id = ?", id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn get_image_page(
+        &self,
+        user_id: Option<i64>,
+        sort: ImageSort,
+        cursor: Option<&ImageCursor>,
+        limit: u32,
+    ) -> sqlx::Result<Vec<DbImageWithUser>> {
+        let cursor_created_at = cursor.map(|cursor| cursor.created_at);
+        let cursor_id = cursor.map(|cursor| cursor.id.as_str());
+
+        match sort {
+            ImageSort::Newest => {
+                query_as!(
+                    DbImageWithUser,
+                    r#"SELECT images.*, users.username AS "username!"
+                    FROM images JOIN users ON users.id = images.user_id
+                    WHERE (?1 IS NULL OR images.user_id = ?1)
+                    AND (?2 IS NULL OR (images.created_at, images.id) < (?2, ?3))
+                    ORDER BY images.created_at DESC, images.id DESC
+                    LIMIT ?4"#,
+                    user_id,
+                    cursor_created_at,
+                    cursor_id,
+                    limit,
+                )
+                .fetch_all(&self.pool)
+                .await
+            }
+            ImageSort::Oldest => {
+                query_as!(
+                    DbImageWithUser,
+                    r#"SELECT images.*, users.username AS "username!"
+                    FROM images JOIN users ON users.id = images.user_id
+                    WHERE (?1 IS NULL OR images.user_id = ?1)
+                    AND (?2 IS NULL OR (images.created_at, images.id) > (?2, ?3))
+                    ORDER BY images.created_at ASC, images.id ASC
+                    LIMIT ?4"#,
+                    user_id,
+                    cursor_created_at,
+                    cursor_id,
+                    limit,
+                )
+                .fetch_all(&self.pool)
+                .await
+            }
+        }
+    }
+
+    async fn delete_image(&self, id: &str) -> sqlx::Result<()> {
+        // Renditions are deleted along with the image.
+        query!("DELETE FROM images WHERE id = ?", id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_images_missing_metadata(&self) -> sqlx::Result<Vec<DbImage>> {
+        query_as!(DbImage, "SELECT * FROM images WHERE size_bytes = 0")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn get_images_missing_renditions(&self) -> sqlx::Result<Vec<DbImage>> {
+        query_as!(
+            DbImage,
+            "SELECT * FROM images WHERE id NOT IN (SELECT image_id FROM image_renditions)"
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn update_image_metadata(
+        &self,
+        id: &str,
+        metadata: &ImageMetadata,
+        created_at: NaiveDateTime,
+    ) -> sqlx::Result<()> {
+        let size_bytes = metadata.size_bytes as i64;
+
+        query!(
+            "UPDATE images SET width = ?, height = ?, size_bytes = ?, created_at = ? WHERE id = ?",
+            metadata.width,
+            metadata.height,
+            size_bytes,
+            created_at,
+            id,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn insert_renditions(
+        &self,
+        image_id: &str,
+        renditions: &[EncodedRendition],
+    ) -> sqlx::Result<()> {
+        let mut transaction = self.pool.begin().await?;
+
+        insert_renditions(&mut transaction, image_id, renditions).await?;
+
+        transaction.commit().await
+    }
+
+    async fn get_renditions(&self, image_id: &str) -> sqlx::Result<Vec<DbRendition>> {
+        query_as!(
+            DbRendition,
+            "SELECT image_id, width, height, extension FROM image_renditions WHERE image_id = ? ORDER BY width",
+            image_id,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn insert_refresh_token(
+        &self,
+        token_hash: &str,
+        user_id: i64,
+        created_at: NaiveDateTime,
+        expires_at: NaiveDateTime,
+    ) -> sqlx::Result<()> {
+        query!(
+            "INSERT INTO refresh_tokens (token_hash, user_id, created_at, expires_at) VALUES (?, ?, ?, ?)",
+            token_hash,
+            user_id,
+            created_at,
+            expires_at,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn consume_refresh_token(
+        &self,
+        token_hash: &str,
+        now: NaiveDateTime,
+    ) -> sqlx::Result<Option<i64>> {
+        query_scalar!(
+            "UPDATE refresh_tokens SET revoked_at = ?1 WHERE token_hash = ?2 AND revoked_at IS NULL AND expires_at > ?1 RETURNING user_id",
+            now,
+            token_hash,
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn revoke_refresh_token(&self, token_hash: &str, now: NaiveDateTime) -> sqlx::Result<()> {
+        query!(
+            "UPDATE refresh_tokens SET revoked_at = ? WHERE token_hash = ? AND revoked_at IS NULL",
+            now,
+            token_hash,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn revoke_user_refresh_tokens(
+        &self,
+        user_id: i64,
+        now: NaiveDateTime,
+    ) -> sqlx::Result<()> {
+        revoke_user_refresh_tokens(&mut *self.pool.acquire().await?, user_id, now).await
+    }
+}
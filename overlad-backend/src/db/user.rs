@@ -3,8 +3,10 @@ use argon2::{
     password_hash::{PasswordHasher, SaltString, rand_core::OsRng},
 };
 use overlad_api::User;
-use sqlx::{SqlitePool, query_as};
 
+use crate::{error::Result, util::spawn_blocking};
+
+#[derive(sqlx::FromRow)]
 pub struct DbUser {
     pub id: i64,
     pub username: String,
@@ -12,42 +14,36 @@ pub struct DbUser {
 }
 
 impl DbUser {
-    pub async fn insert(pool: &SqlitePool, username: &str, password: &str) -> sqlx::Result<Self> {
-        let salt = SaltString::generate(&mut OsRng);
-
-        let passhash = Argon2::default()
-            .hash_password(password.as_bytes(), &salt)
-            .unwrap()
-            .to_string();
-
-        query_as!(
-            Self,
-            "INSERT INTO users (username, passhash) VALUES (?, ?) RETURNING *",
-            username,
-            passhash,
-        )
-        .fetch_one(pool)
+    /// Checks `password` against the stored hash on the blocking thread pool,
+    /// since Argon2 is deliberately slow.
+    pub async fn verify_password(&self, password: &str) -> Result<bool> {
+        let passhash = self.passhash.clone();
+        let password = password.to_owned();
+
+        spawn_blocking(move || {
+            let passhash = PasswordHash::new(&passhash).unwrap();
+
+            Ok(Argon2::default()
+                .verify_password(password.as_bytes(), &passhash)
+                .is_ok())
+        })
         .await
     }
+}
 
-    pub async fn get_by_id(pool: &SqlitePool, id: i64) -> sqlx::Result<Option<Self>> {
-        query_as!(Self, "SELECT * FROM users WHERE id = ?", id,)
-            .fetch_optional(pool)
-            .await
-    }
+/// Hashes `password` with a new salt on the blocking thread pool.
+pub async fn hash_password(password: &str) -> Result<String> {
+    let password = password.to_owned();
 
-    pub async fn get_by_username(pool: &SqlitePool, username: &str) -> sqlx::Result<Option<Self>> {
-        query_as!(Self, "SELECT * FROM users WHERE username = ?", username,)
-            .fetch_optional(pool)
-            .await
-    }
+    spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
 
-    pub fn verify_password(&self, password: &str) -> bool {
-        let passhash = PasswordHash::new(&self.passhash).unwrap();
-        Argon2::default()
-            .verify_password(password.as_bytes(), &passhash)
-            .is_ok()
-    }
+        Ok(Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .unwrap()
+            .to_string())
+    })
+    .await
 }
 
 impl From<DbUser> for User {
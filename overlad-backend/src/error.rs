@@ -0,0 +1,179 @@
+use std::{collections::BTreeMap, fmt::Display, io, time::Duration};
+
+use axum::{
+    Json,
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::{HeaderValue, StatusCode, header::RETRY_AFTER},
+    response::{IntoResponse, Response},
+};
+use image::ImageError;
+use overlad_api::ErrorResponse;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// An error returned by a handler, sent to the client as an [`ErrorResponse`].
+#[derive(Debug)]
+pub enum Error {
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    PayloadTooLarge(String),
+    UnsupportedMediaType(String),
+    /// Request fields that failed validation, keyed by field name.
+    Validation(BTreeMap<String, String>),
+    /// Rate limited, with how long until the client may try again.
+    TooManyRequests(Duration),
+    /// A failure on our side, whose details are logged but never sent.
+    Internal(String),
+}
+
+impl Error {
+    pub fn internal(error: impl Display) -> Self {
+        Self::Internal(format!("{error}"))
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Self::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Self::BadRequest(message)
+            | Self::Unauthorized(message)
+            | Self::Forbidden(message)
+            | Self::NotFound(message)
+            | Self::Conflict(message)
+            | Self::PayloadTooLarge(message)
+            | Self::UnsupportedMediaType(message) => message,
+            Self::Validation(_) => "invalid request",
+            Self::TooManyRequests(_) => "too many attempts, try again later",
+            Self::Internal(_) => "internal server error",
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Internal(message) => write!(f, "{message}"),
+            _ => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        if let Self::Internal(message) = &self {
+            tracing::error!("{message}");
+        }
+
+        let status_code = self.status_code();
+        let error = self.message().to_owned();
+
+        // Retry-After is in whole seconds, so round up to not invite an early
+        // retry.
+        let maybe_retry_after = match &self {
+            Self::TooManyRequests(retry_after) => Some(retry_after.as_secs_f64().ceil() as u64),
+            _ => None,
+        };
+
+        let error_response = ErrorResponse {
+            error,
+            fields: match self {
+                Self::Validation(fields) => fields,
+                _ => BTreeMap::new(),
+            },
+        };
+
+        let mut response = (status_code, Json(error_response)).into_response();
+
+        if let Some(retry_after) = maybe_retry_after {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+
+        response
+    }
+}
+
+impl Error {
+    /// Keeps the status of a rejected extractor, falling back to a bad request
+    /// for the statuses there is no variant for.
+    fn from_rejection(status_code: StatusCode, message: String) -> Self {
+        match status_code {
+            StatusCode::PAYLOAD_TOO_LARGE => Self::PayloadTooLarge(message),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => Self::UnsupportedMediaType(message),
+            status_code if status_code.is_server_error() => Self::Internal(message),
+            _ => Self::BadRequest(message),
+        }
+    }
+}
+
+impl From<JsonRejection> for Error {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::from_rejection(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for Error {
+    fn from(rejection: QueryRejection) -> Self {
+        Self::from_rejection(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for Error {
+    fn from(rejection: PathRejection) -> Self {
+        Self::from_rejection(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(error: sqlx::Error) -> Self {
+        match error {
+            sqlx::Error::RowNotFound => Self::NotFound(String::from("not found")),
+            error => Self::internal(error),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::NotFound => Self::NotFound(String::from("file not found")),
+            _ => Self::internal(error),
+        }
+    }
+}
+
+impl From<ImageError> for Error {
+    fn from(error: ImageError) -> Self {
+        match error {
+            ImageError::Decoding(_) => Self::BadRequest(format!("{error}")),
+            // A truncated upload surfaces as an unexpected end of file.
+            ImageError::IoError(ref io_error)
+                if io_error.kind() == io::ErrorKind::UnexpectedEof =>
+            {
+                Self::BadRequest(format!("{error}"))
+            }
+            ImageError::Limits(_) => Self::PayloadTooLarge(format!("{error}")),
+            ImageError::Unsupported(_) => Self::UnsupportedMediaType(format!("{error}")),
+            error => Self::internal(error),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    extract::DefaultBodyLimit,
+    routing::{get, post},
+};
+use hmac::Hmac;
+use overlad_lib::font::FontRegistry;
+use sha2::Sha256;
+use tower_http::trace::TraceLayer;
+
+use crate::{
+    api::{
+        all_images::all_images,
+        fonts::{all_fonts, get_font},
+        image::{delete_image, get_image},
+        me::{change_password, get_me},
+        overlay::get_overlay,
+        register::register,
+        renditions::rendition_widths,
+        token::{logout, refresh, token},
+        upload::upload,
+        user::get_user,
+        user_images::user_images,
+    },
+    cache::OverlayCache,
+    cors::{CorsRules, public_layer},
+    db::Repository,
+    rate_limit::RateLimiter,
+    rendition::RenditionRules,
+    storage::Storage,
+    upload_limits::UploadLimits,
+    validation::ValidationRules,
+};
+
+mod api;
+mod auth;
+pub mod backfill;
+pub mod cache;
+pub mod client_ip;
+pub mod cors;
+pub mod db;
+mod error;
+mod extract;
+pub mod rate_limit;
+pub mod rendition;
+pub mod storage;
+pub mod upload_limits;
+mod util;
+pub mod validation;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub key: Hmac<Sha256>,
+    pub db: Arc<dyn Repository>,
+    pub storage: Arc<dyn Storage>,
+    pub fonts: Arc<FontRegistry>,
+    pub overlay_cache: Arc<OverlayCache>,
+    pub overlay_max_age: u64,
+    pub max_output_dimension: u32,
+    pub max_text_scale: f64,
+    pub upload_limits: UploadLimits,
+    pub rendition_rules: RenditionRules,
+    pub access_token_ttl: u64,
+    pub refresh_token_ttl: u64,
+    pub validation_rules: ValidationRules,
+    pub auth_rate_limiter: Arc<RateLimiter>,
+    pub forwarded_header: Option<String>,
+    pub cors_rules: CorsRules,
+}
+
+/// Room in the body limit for the multipart boundaries and headers around an
+/// upload of the largest allowed size.
+const MULTIPART_OVERHEAD: usize = 64 * 1024;
+
+/// Routes every request the backend serves.
+pub fn router(state: AppState) -> Router {
+    let body_limit = state.upload_limits.max_bytes + MULTIPART_OVERHEAD;
+
+    // Overlays are meant to be embedded, so any origin may fetch them.
+    let public_routes = Router::new()
+        .route("/overlay/{id}", get(get_overlay))
+        .layer(public_layer());
+
+    Router::new()
+        .route("/register", post(register))
+        .route("/token", post(token))
+        .route("/token/refresh", post(refresh))
+        .route("/logout", post(logout))
+        .route("/me", get(get_me))
+        .route("/me/password", post(change_password))
+        .route("/upload", post(upload))
+        .route("/all_images", get(all_images))
+        .route("/fonts", get(all_fonts))
+        .route("/fonts/{name}", get(get_font))
+        .route("/renditions", get(rendition_widths))
+        .route("/user/{user_id}", get(get_user))
+        .route("/user/{user_id}/images", get(user_images))
+        .route("/image/{id}", get(get_image).delete(delete_image))
+        .layer(state.cors_rules.layer())
+        .merge(public_routes)
+        .layer(DefaultBodyLimit::max(body_limit))
+        .layer(TraceLayer::new_for_http())
+        .with_state(state)
+}
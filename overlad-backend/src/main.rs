@@ -1,56 +1,382 @@
-use std::{fmt::Debug, fs::Permissions, os::unix::fs::PermissionsExt, path::PathBuf};
+use std::{fmt::Debug, fs::Permissions, net::IpAddr, os::unix::fs::PermissionsExt, path::{Path, PathBuf}, sync::Arc, time::Duration};
 
 use axum::{
-    extract::DefaultBodyLimit,
-    http::header::{AUTHORIZATION, CONTENT_TYPE},
-    routing::{get, post},
-    serve::Listener,
+    extract::connect_info::Connected,
+    http::{HeaderName, HeaderValue, Method},
+    serve::{IncomingStream, Listener},
 };
-use clap::{Args, Parser};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use dotenvy::dotenv;
 use hmac::{Hmac, Mac};
-use sha2::Sha256;
-use sqlx::SqlitePool;
+use image::ImageFormat;
+use overlad_api::DEFAULT_FONT;
+use overlad_backend::{AppState, backfill, cache::OverlayCache, client_ip::PeerAddr, cors::{CorsRules, parse_method, parse_origin}, db::{self, Repository}, rate_limit::{RateLimitRules, RateLimiter}, rendition::{RenditionEncoding, RenditionRules}, router, storage::{Storage, filesystem::FilesystemStorage, s3::{S3Config, S3Storage}}, upload_limits::{UploadLimits, parse_image_format}, validation::ValidationRules};
+use overlad_lib::font::FontRegistry;
 use tokio::net::{TcpListener, UnixListener};
-use tower_http::cors::CorsLayer;
+use tracing::Level;
 
-use crate::api::{
-    all_images::all_images, image::get_image, overlay::get_overlay, register::register, token::token, upload::upload, user::get_user, user_images::user_images
-};
+use crate::config::Config;
 
-mod api;
-mod db;
-mod util;
+mod config;
 
 #[derive(Parser)]
+#[command(subcommand_negates_reqs = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     #[command(flatten)]
     listen: Listen,
+
+    /// Address to listen on with --port
+    #[arg(long, env = "BIND", default_value = "0.0.0.0")]
+    bind: IpAddr,
+
+    /// Secret access tokens are signed with
+    #[arg(long, env = "KEY", hide_env_values = true)]
+    key: Option<String>,
+
+    /// Database to connect to, Postgres for postgres:// URLs and SQLite
+    /// otherwise, such as sqlite://overlad.db
+    #[arg(long, env = "DATABASE_URL", hide_env_values = true)]
+    database_url: Option<String>,
+
+    #[command(flatten)]
+    storage: StorageArgs,
+
+    /// Apply pending database migrations before serving
+    #[arg(long, env = "MIGRATE")]
+    migrate: bool,
+
+    /// Directory of TTF/OTF files to register alongside the built-in font
+    #[arg(long, env = "FONTS", default_value = "fonts")]
+    fonts: PathBuf,
+
+    /// Maximum number of rendered overlays kept in memory, 0 disables the cache
+    #[arg(long, env = "OVERLAY_CACHE_ENTRIES", default_value_t = 1024)]
+    overlay_cache_entries: usize,
+
+    /// Maximum total size in bytes of rendered overlays kept in memory
+    #[arg(long, env = "OVERLAY_CACHE_BYTES", default_value_t = 64 * 1024 * 1024)]
+    overlay_cache_bytes: usize,
+
+    /// Max-age in seconds sent in the Cache-Control header of overlays. Browsers
+    /// and proxies may keep serving an overlay this long after its image is
+    /// deleted
+    #[arg(long, env = "OVERLAY_MAX_AGE", default_value_t = 3600)]
+    overlay_max_age: u64,
+
+    /// Largest width or height in pixels an overlay may be resized to
+    #[arg(long, env = "MAX_OUTPUT_DIMENSION", default_value_t = 4096)]
+    max_output_dimension: u32,
+
+    /// Largest text scale an overlay may use, where 1 is a tenth of the
+    /// image's shorter side
+    #[arg(long, env = "MAX_TEXT_SCALE", default_value_t = 10.0)]
+    max_text_scale: f64,
+
+    /// Largest upload in bytes, which also limits the size of request bodies
+    #[arg(long, env = "MAX_UPLOAD_BYTES", default_value_t = 8_000_000)]
+    max_upload_bytes: usize,
+
+    /// Widest upload in pixels
+    #[arg(long, env = "MAX_UPLOAD_WIDTH", default_value_t = 8192)]
+    max_upload_width: u32,
+
+    /// Tallest upload in pixels
+    #[arg(long, env = "MAX_UPLOAD_HEIGHT", default_value_t = 8192)]
+    max_upload_height: u32,
+
+    /// Most pixels an upload may have, or each frame of an animated upload
+    #[arg(long, env = "MAX_UPLOAD_PIXELS", default_value_t = 40_000_000)]
+    max_upload_pixels: u64,
+
+    /// Most frames an animated upload may have
+    #[arg(long, env = "MAX_UPLOAD_FRAMES", default_value_t = 1000)]
+    max_upload_frames: usize,
+
+    /// Most pixels across all frames of an animation, checked on upload, when
+    /// decoding it to render and against the resized frames
+    #[arg(long, env = "MAX_ANIMATION_PIXELS", default_value_t = 100_000_000)]
+    max_animation_pixels: u64,
+
+    /// Comma separated image formats accepted for upload, by name or extension
+    #[arg(
+        long,
+        env = "UPLOAD_FORMATS",
+        value_delimiter = ',',
+        default_value = "png,jpeg,gif,webp",
+        value_parser = parse_image_format,
+    )]
+    upload_formats: Vec<ImageFormat>,
+
+    /// Comma separated widths in pixels of the downscaled renditions kept of
+    /// each still, alongside the full size one
+    #[arg(
+        long,
+        env = "RENDITION_WIDTHS",
+        value_delimiter = ',',
+        default_value = "2048,1024,512,256"
+    )]
+    rendition_widths: Vec<u32>,
+
+    /// How renditions of stills are encoded
+    #[arg(
+        long,
+        env = "RENDITION_ENCODING",
+        value_enum,
+        default_value_t = RenditionEncoding::Lossless
+    )]
+    rendition_encoding: RenditionEncoding,
+
+    /// Quality from 1 to 100 of lossy renditions
+    #[arg(
+        long,
+        env = "RENDITION_QUALITY",
+        default_value_t = 85,
+        value_parser = clap::value_parser!(u8).range(1..=100)
+    )]
+    rendition_quality: u8,
+
+    /// Lifetime in seconds of access tokens
+    #[arg(long, env = "ACCESS_TOKEN_TTL", default_value_t = 15 * 60)]
+    access_token_ttl: u64,
+
+    /// Lifetime in seconds of refresh tokens
+    #[arg(long, env = "REFRESH_TOKEN_TTL", default_value_t = 30 * 24 * 60 * 60)]
+    refresh_token_ttl: u64,
+
+    /// Shortest username in characters allowed on registration
+    #[arg(long, env = "MIN_USERNAME_LENGTH", default_value_t = 3)]
+    min_username_length: usize,
+
+    /// Longest username in characters allowed on registration
+    #[arg(long, env = "MAX_USERNAME_LENGTH", default_value_t = 32)]
+    max_username_length: usize,
+
+    /// Shortest password in characters allowed
+    #[arg(long, env = "MIN_PASSWORD_LENGTH", default_value_t = 8)]
+    min_password_length: usize,
+
+    /// Longest password in characters allowed
+    #[arg(long, env = "MAX_PASSWORD_LENGTH", default_value_t = 256)]
+    max_password_length: usize,
+
+    /// Login and registration attempts allowed per client IP and per username
+    /// within the rate limit window
+    #[arg(long, env = "AUTH_MAX_ATTEMPTS", default_value_t = 10)]
+    auth_max_attempts: usize,
+
+    /// Length in seconds of the login and registration rate limit window
+    #[arg(long, env = "AUTH_WINDOW", default_value_t = 60)]
+    auth_window: u64,
+
+    /// Failed logins within the rate limit window before a client IP or
+    /// username is locked out
+    #[arg(long, env = "LOCKOUT_FAILURES", default_value_t = 5)]
+    lockout_failures: usize,
+
+    /// Length in seconds of a lockout after repeated failed logins
+    #[arg(long, env = "LOCKOUT_DURATION", default_value_t = 15 * 60)]
+    lockout_duration: u64,
+
+    /// Header set by a trusted reverse proxy to the client IP, such as
+    /// X-Forwarded-For, needed to rate limit by IP behind a Unix socket
+    #[arg(long, env = "FORWARDED_HEADER")]
+    forwarded_header: Option<String>,
+
+    /// Comma separated origins browsers may call the API from, such as
+    /// https://overlad.example, or * for any. Overlays may be fetched from any
+    /// origin regardless
+    #[arg(
+        long,
+        env = "CORS_ORIGINS",
+        value_delimiter = ',',
+        default_value = "http://localhost:3000",
+        value_parser = parse_origin,
+    )]
+    cors_origins: Vec<HeaderValue>,
+
+    /// Comma separated methods allowed in cross-origin API requests
+    #[arg(
+        long,
+        env = "CORS_METHODS",
+        value_delimiter = ',',
+        default_value = "GET,POST,DELETE",
+        value_parser = parse_method,
+    )]
+    cors_methods: Vec<Method>,
+
+    /// Comma separated headers allowed in cross-origin API requests
+    #[arg(
+        long,
+        env = "CORS_HEADERS",
+        value_delimiter = ',',
+        default_value = "authorization,content-type"
+    )]
+    cors_headers: Vec<HeaderName>,
+
+    /// Most verbose level of messages logged to stderr: error, warn, info,
+    /// debug or trace
+    #[arg(long, env = "LOG_LEVEL", default_value_t = Level::INFO)]
+    log_level: Level,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Fill in the dimensions, size and upload time of images stored before
+    /// they were recorded, and generate renditions of those stored before
+    /// renditions were kept
+    Backfill,
+
+    /// Manage the database schema
+    Migrate {
+        #[command(subcommand)]
+        command: MigrateCommand,
+    },
+
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum MigrateCommand {
+    /// Apply all pending migrations
+    Up,
+
+    /// Revert the latest applied migration
+    Down,
+
+    /// List the migrations and whether each is applied
+    Status,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the effective configuration and check that it is valid
+    Check,
 }
 
+// One of these is required when serving, which is checked after parsing since
+// values from the config file do not count as given.
 #[derive(Args)]
-#[group(required = true, multiple = false)]
+#[group(multiple = false)]
 struct Listen {
-    #[arg(short, long, group = "listen")]
+    #[arg(short, long, env = "PORT", group = "listen")]
     port: Option<u16>,
 
-    #[arg(short, long, group = "listen")]
+    #[arg(short, long, env = "UDS", group = "listen")]
     uds: Option<PathBuf>,
 }
 
-#[derive(Clone)]
-pub struct AppState {
-    key: Hmac<Sha256>,
-    pool: SqlitePool,
+#[derive(Args)]
+struct StorageArgs {
+    /// Where image files are stored
+    #[arg(
+        id = "storage",
+        long = "storage",
+        env = "STORAGE",
+        value_enum,
+        default_value_t = StorageKind::Filesystem
+    )]
+    kind: StorageKind,
+
+    /// Directory image files are stored in with filesystem storage
+    #[arg(long, env = "STORAGE_ROOT", default_value = "images")]
+    storage_root: PathBuf,
+
+    /// URL of the S3-compatible service with s3 storage, such as
+    /// http://localhost:9000 or https://s3.us-east-1.amazonaws.com
+    #[arg(long, env = "S3_ENDPOINT")]
+    s3_endpoint: Option<String>,
+
+    /// Bucket image files are stored in with s3 storage
+    #[arg(long, env = "S3_BUCKET")]
+    s3_bucket: Option<String>,
+
+    /// Region the bucket is in with s3 storage
+    #[arg(long, env = "S3_REGION", default_value = "us-east-1")]
+    s3_region: String,
+
+    #[arg(long, env = "S3_ACCESS_KEY_ID")]
+    s3_access_key_id: Option<String>,
+
+    #[arg(long, env = "S3_SECRET_ACCESS_KEY", hide_env_values = true)]
+    s3_secret_access_key: Option<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum StorageKind {
+    Filesystem,
+    S3,
 }
 
 #[tokio::main]
 async fn main() {
-    let cli = Cli::parse();
-    dotenv().unwrap();
+    // Loaded first so `.env` can set the options that read the environment.
+    dotenv().ok();
+    let (cli, config) = Config::parse::<Cli>();
+
+    let serving = matches!(cli.command, None | Some(Command::Config { .. }));
+    let validation = validate(&cli, serving);
+
+    if let Some(Command::Config {
+        command: ConfigCommand::Check,
+    }) = cli.command
+    {
+        config.print();
+
+        match validation {
+            Ok(()) => println!("# configuration is valid"),
+            Err(error) => config.exit_with_error(error),
+        }
+        return;
+    }
+
+    if let Err(error) = validation {
+        config.exit_with_error(error);
+    }
+
+    tracing_subscriber::fmt()
+        .with_max_level(cli.log_level)
+        .with_writer(std::io::stderr)
+        .init();
+
+    match &cli.command {
+        Some(Command::Backfill) => {
+            let db = connect(&cli).await;
+            let storage = storage(&cli.storage).unwrap();
+
+            backfill::backfill(&*db, &*storage).await.unwrap();
+            backfill::backfill_renditions(
+                &*db,
+                &*storage,
+                &upload_limits(&cli),
+                &rendition_rules(&cli),
+            )
+            .await
+            .unwrap();
+            return;
+        }
+        Some(Command::Migrate { command }) => {
+            migrate(&*connect(&cli).await, command).await;
+            return;
+        }
+        Some(Command::Config { .. }) | None => {}
+    }
+
+    let fonts = load_fonts(&cli.fonts).unwrap_or_else(|error| config.exit_with_error(error));
+    let state = app_state(&cli, fonts).await;
 
     if let Some(port) = cli.listen.port {
-        serve_with_listener(TcpListener::bind(format!("0.0.0.0:{port}")).await.unwrap()).await;
+        serve_with_listener(
+            TcpListener::bind((cli.bind, port)).await.unwrap(),
+            state,
+        )
+        .await;
     } else if let Some(path) = cli.listen.uds {
         let _ = tokio::fs::remove_file(&path).await;
         tokio::fs::create_dir_all(path.parent().unwrap())
@@ -63,43 +389,207 @@ async fn main() {
             .await
             .unwrap();
 
-        serve_with_listener(listener).await;
+        serve_with_listener(listener, state).await;
+    }
+}
+
+/// Checks what the parser cannot, describing the first problem found.
+/// Serving needs more options than the other commands.
+fn validate(cli: &Cli, serving: bool) -> Result<(), String> {
+    if cli.database_url.is_none() {
+        return Err(String::from(
+            "DATABASE_URL or --database-url is required",
+        ));
     }
+
+    if serving {
+        if cli.key.as_deref().unwrap_or_default().is_empty() {
+            return Err(String::from("KEY or --key is required"));
+        }
+
+        if cli.listen.port.is_some() == cli.listen.uds.is_some() {
+            return Err(String::from("exactly one of --port or --uds is required"));
+        }
+    }
+
+    if cli.min_username_length > cli.max_username_length {
+        return Err(String::from(
+            "--min-username-length must not be greater than --max-username-length",
+        ));
+    }
+
+    if cli.min_password_length > cli.max_password_length {
+        return Err(String::from(
+            "--min-password-length must not be greater than --max-password-length",
+        ));
+    }
+
+    if !(cli.max_text_scale.is_finite() && cli.max_text_scale > 0.0) {
+        return Err(String::from("--max-text-scale must be greater than 0"));
+    }
+
+    if cli.rendition_widths.contains(&0) {
+        return Err(String::from("--rendition-widths must not contain 0"));
+    }
+
+    storage(&cli.storage).map(drop)
 }
 
-async fn serve_with_listener<L>(listener: L)
+async fn connect(cli: &Cli) -> Arc<dyn Repository> {
+    db::connect(cli.database_url.as_deref().unwrap())
+        .await
+        .unwrap()
+}
+
+async fn migrate(db: &dyn Repository, command: &MigrateCommand) {
+    match command {
+        MigrateCommand::Up => {
+            db.migrate_up().await.unwrap();
+            println!("database is up to date");
+        }
+        MigrateCommand::Down => match db.migrate_down().await.unwrap() {
+            Some(version) => println!("reverted migration {version}"),
+            None => println!("no migrations to revert"),
+        },
+        MigrateCommand::Status => {
+            for migration_status in db.migration_status().await.unwrap() {
+                println!(
+                    "{} {} {}",
+                    migration_status.version,
+                    if migration_status.applied {
+                        "applied"
+                    } else {
+                        "pending"
+                    },
+                    migration_status.description,
+                );
+            }
+        }
+    }
+}
+
+async fn app_state(cli: &Cli, fonts: FontRegistry) -> AppState {
+    let db = connect(cli).await;
+
+    if cli.migrate {
+        db.migrate_up().await.unwrap();
+    }
+
+    AppState {
+        key: Hmac::new_from_slice(cli.key.as_deref().unwrap().as_bytes()).unwrap(),
+        db,
+        storage: storage(&cli.storage).unwrap(),
+        fonts: Arc::new(fonts),
+        overlay_cache: Arc::new(OverlayCache::new(
+            cli.overlay_cache_entries,
+            cli.overlay_cache_bytes,
+        )),
+        overlay_max_age: cli.overlay_max_age,
+        max_output_dimension: cli.max_output_dimension,
+        max_text_scale: cli.max_text_scale,
+        upload_limits: upload_limits(cli),
+        rendition_rules: rendition_rules(cli),
+        access_token_ttl: cli.access_token_ttl,
+        refresh_token_ttl: cli.refresh_token_ttl,
+        validation_rules: ValidationRules {
+            min_username_length: cli.min_username_length,
+            max_username_length: cli.max_username_length,
+            min_password_length: cli.min_password_length,
+            max_password_length: cli.max_password_length,
+        },
+        auth_rate_limiter: Arc::new(RateLimiter::new(RateLimitRules {
+            max_attempts: cli.auth_max_attempts,
+            window: Duration::from_secs(cli.auth_window),
+            max_failures: cli.lockout_failures,
+            lockout: Duration::from_secs(cli.lockout_duration),
+        })),
+        forwarded_header: cli.forwarded_header.clone(),
+        cors_rules: CorsRules {
+            origins: cli.cors_origins.clone(),
+            methods: cli.cors_methods.clone(),
+            headers: cli.cors_headers.clone(),
+        },
+    }
+}
+
+fn storage(args: &StorageArgs) -> Result<Arc<dyn Storage>, String> {
+    match args.kind {
+        StorageKind::Filesystem => Ok(Arc::new(FilesystemStorage::new(
+            args.storage_root.clone(),
+        ))),
+        StorageKind::S3 => {
+            // Checked here rather than by the parser so `config check` can
+            // print the configuration before reporting what is missing.
+            let required = |value: &Option<String>, name: &str| {
+                value
+                    .clone()
+                    .ok_or_else(|| format!("--{name} is required with s3 storage"))
+            };
+
+            let config = S3Config {
+                endpoint: required(&args.s3_endpoint, "s3-endpoint")?,
+                bucket: required(&args.s3_bucket, "s3-bucket")?,
+                region: args.s3_region.clone(),
+                access_key_id: required(&args.s3_access_key_id, "s3-access-key-id")?,
+                secret_access_key: required(&args.s3_secret_access_key, "s3-secret-access-key")?,
+            };
+
+            Ok(Arc::new(S3Storage::new(config)?))
+        }
+    }
+}
+
+fn upload_limits(cli: &Cli) -> UploadLimits {
+    UploadLimits {
+        max_bytes: cli.max_upload_bytes,
+        max_width: cli.max_upload_width,
+        max_height: cli.max_upload_height,
+        max_pixels: cli.max_upload_pixels,
+        max_frames: cli.max_upload_frames,
+        max_animation_pixels: cli.max_animation_pixels,
+        formats: cli.upload_formats.clone(),
+    }
+}
+
+fn rendition_rules(cli: &Cli) -> RenditionRules {
+    RenditionRules {
+        widths: cli.rendition_widths.clone(),
+        encoding: cli.rendition_encoding,
+        quality: cli.rendition_quality,
+    }
+}
+
+/// Loads the built-in font and those in `path`, describing the first font that
+/// could not be loaded.
+fn load_fonts(path: &Path) -> Result<FontRegistry, String> {
+    let mut fonts = FontRegistry::new();
+
+    fonts
+        .insert(DEFAULT_FONT, include_bytes!("../../roboto.ttf").to_vec(), "ttf")
+        .expect("the built-in font is valid");
+
+    if path.is_dir() {
+        fonts
+            .load_dir(path)
+            .map_err(|error| format!("could not load fonts: {error}"))?;
+    }
+
+    Ok(fonts)
+}
+
+
+async fn serve_with_listener<L>(listener: L, state: AppState)
 where
     L: Listener,
     L::Addr: Debug,
+    for<'a> PeerAddr: Connected<IncomingStream<'a, L>>,
 {
-    let pool = SqlitePool::connect(
-        &std::env::var("DATABASE_URL").expect("DATABASE_URL environment variable not set"),
+    tracing::info!("listening on {:?}", listener.local_addr().unwrap());
+
+    axum::serve(
+        listener,
+        router(state).into_make_service_with_connect_info::<PeerAddr>(),
     )
     .await
     .unwrap();
-
-    let state = AppState {
-        key: Hmac::new_from_slice(
-            std::env::var("KEY")
-                .expect("KEY environment variable not set")
-                .as_bytes(),
-        )
-        .unwrap(),
-        pool,
-    };
-
-    let app = axum::Router::new()
-        .route("/register", post(register))
-        .route("/token", post(token))
-        .route("/upload", post(upload))
-        .route("/overlay/{id}", get(get_overlay))
-        .route("/all_images", get(all_images))
-        .route("/user/{user_id}", get(get_user))
-        .route("/user/{user_id}/images", get(user_images))
-        .route("/image/{id}", get(get_image))
-        .layer(DefaultBodyLimit::max(8000000))
-        .layer(CorsLayer::permissive().allow_headers([AUTHORIZATION, CONTENT_TYPE]))
-        .with_state(state);
-
-    axum::serve(listener, app).await.unwrap();
 }
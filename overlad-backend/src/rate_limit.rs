@@ -0,0 +1,309 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::error::{Error, Result};
+
+/// Sliding window limits on attempts, plus a temporary lockout after repeated
+/// failures.
+#[derive(Debug, Clone)]
+pub struct RateLimitRules {
+    pub max_attempts: usize,
+    pub window: Duration,
+    pub max_failures: usize,
+    pub lockout: Duration,
+}
+
+/// Throttles authentication attempts per client IP and per username.
+pub struct RateLimiter {
+    rules: RateLimitRules,
+    entries: Mutex<Entries>,
+}
+
+/// The tracked keys, whose idle entries are dropped at most once per window
+/// so a flood of new keys does not make every check walk all of them.
+struct Entries {
+    by_key: HashMap<String, Entry>,
+    last_sweep: Instant,
+}
+
+#[derive(Default)]
+struct Entry {
+    attempts: VecDeque<Instant>,
+    failures: VecDeque<Instant>,
+    locked_until: Option<Instant>,
+}
+
+impl Entry {
+    fn prune(&mut self, now: Instant, window: Duration) {
+        while self
+            .attempts
+            .front()
+            .is_some_and(|attempt| now.duration_since(*attempt) >= window)
+        {
+            self.attempts.pop_front();
+        }
+
+        while self
+            .failures
+            .front()
+            .is_some_and(|failure| now.duration_since(*failure) >= window)
+        {
+            self.failures.pop_front();
+        }
+
+        if self
+            .locked_until
+            .is_some_and(|locked_until| locked_until <= now)
+        {
+            self.locked_until = None;
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.attempts.is_empty() && self.failures.is_empty() && self.locked_until.is_none()
+    }
+}
+
+impl RateLimiter {
+    pub fn new(rules: RateLimitRules) -> Self {
+        Self {
+            rules,
+            entries: Mutex::new(Entries {
+                by_key: HashMap::new(),
+                last_sweep: Instant::now(),
+            }),
+        }
+    }
+
+    /// Records an attempt for every key, or rejects with the time until the
+    /// first of them may try again if any is over its limit or locked out.
+    pub fn check(&self, keys: &[String]) -> Result<()> {
+        self.check_at(keys, Instant::now())
+    }
+
+    /// Counts a failed attempt against every key, locking out those that
+    /// reach the failure limit within the window.
+    pub fn fail(&self, keys: &[String]) {
+        self.fail_at(keys, Instant::now());
+    }
+
+    fn check_at(&self, keys: &[String], now: Instant) -> Result<()> {
+        let mut guard = self.entries.lock().unwrap();
+        let entries = &mut *guard;
+
+        if now.duration_since(entries.last_sweep) >= self.rules.window {
+            entries.by_key.retain(|_, entry| {
+                entry.prune(now, self.rules.window);
+                !entry.is_idle()
+            });
+            entries.last_sweep = now;
+        }
+
+        let mut retry_after = Duration::ZERO;
+
+        // Keys without an entry have no attempts to be over the limit with,
+        // and rejected attempts do not add one.
+        for key in keys {
+            let Some(entry) = entries.by_key.get_mut(key) else {
+                continue;
+            };
+            entry.prune(now, self.rules.window);
+
+            if let Some(locked_until) = entry.locked_until {
+                retry_after = retry_after.max(locked_until - now);
+            } else if entry.attempts.len() >= self.rules.max_attempts
+                && let Some(oldest) = entry.attempts.front()
+            {
+                retry_after = retry_after.max(self.rules.window - now.duration_since(*oldest));
+            }
+        }
+
+        if !retry_after.is_zero() {
+            return Err(Error::TooManyRequests(retry_after));
+        }
+
+        for key in keys {
+            entries
+                .by_key
+                .entry(key.clone())
+                .or_default()
+                .attempts
+                .push_back(now);
+        }
+
+        Ok(())
+    }
+
+    fn fail_at(&self, keys: &[String], now: Instant) {
+        let mut entries = self.entries.lock().unwrap();
+
+        for key in keys {
+            let entry = entries.by_key.entry(key.clone()).or_default();
+            entry.prune(now, self.rules.window);
+            entry.failures.push_back(now);
+
+            if entry.failures.len() >= self.rules.max_failures {
+                entry.failures.clear();
+                entry.locked_until = Some(now + self.rules.lockout);
+            }
+        }
+    }
+
+    /// Forgets the failures of every key after a successful attempt.
+    pub fn succeed(&self, keys: &[String]) {
+        let mut entries = self.entries.lock().unwrap();
+
+        for key in keys {
+            if let Some(entry) = entries.by_key.get_mut(key) {
+                entry.failures.clear();
+            }
+        }
+    }
+}
+
+/// The limiter keys for a request from `client_ip` about `username`.
+pub fn rate_limit_keys(client_ip: Option<IpAddr>, username: &str) -> Vec<String> {
+    let mut keys = vec![format!("user:{}", username.to_lowercase())];
+
+    if let Some(client_ip) = client_ip {
+        keys.push(format!("ip:{client_ip}"));
+    }
+
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_secs(60);
+    const LOCKOUT: Duration = Duration::from_secs(300);
+
+    fn limiter() -> RateLimiter {
+        RateLimiter::new(RateLimitRules {
+            max_attempts: 3,
+            window: WINDOW,
+            max_failures: 2,
+            lockout: LOCKOUT,
+        })
+    }
+
+    fn keys(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    fn retry_after(result: Result<()>) -> Duration {
+        match result {
+            Err(Error::TooManyRequests(retry_after)) => retry_after,
+            _ => panic!("attempt was not rate limited"),
+        }
+    }
+
+    #[test]
+    fn attempts_are_allowed_again_once_the_window_passes() {
+        let limiter = limiter();
+        let keys = keys(&["user:alice"]);
+        let start = Instant::now();
+
+        for second in 0..3 {
+            limiter
+                .check_at(&keys, start + Duration::from_secs(second))
+                .unwrap();
+        }
+
+        let later = start + Duration::from_secs(10);
+        assert_eq!(
+            retry_after(limiter.check_at(&keys, later)),
+            WINDOW - Duration::from_secs(10)
+        );
+
+        // Only the first attempt has left the window.
+        limiter.check_at(&keys, start + WINDOW).unwrap();
+        assert!(limiter.check_at(&keys, start + WINDOW).is_err());
+    }
+
+    #[test]
+    fn failures_lock_out_until_the_lockout_passes() {
+        let limiter = limiter();
+        let keys = keys(&["user:alice", "ip:127.0.0.1"]);
+        let start = Instant::now();
+
+        limiter.check_at(&keys, start).unwrap();
+        limiter.fail_at(&keys, start);
+        limiter.check_at(&keys, start).unwrap();
+        limiter.fail_at(&keys, start);
+
+        assert_eq!(retry_after(limiter.check_at(&keys, start)), LOCKOUT);
+        assert_eq!(
+            retry_after(limiter.check_at(&keys, start + LOCKOUT - Duration::from_secs(1))),
+            Duration::from_secs(1)
+        );
+
+        limiter.check_at(&keys, start + LOCKOUT).unwrap();
+    }
+
+    #[test]
+    fn success_forgets_failures() {
+        let limiter = limiter();
+        let keys = keys(&["user:alice"]);
+        let start = Instant::now();
+
+        limiter.fail_at(&keys, start);
+        limiter.succeed(&keys);
+        limiter.fail_at(&keys, start);
+
+        limiter.check_at(&keys, start).unwrap();
+    }
+
+    #[test]
+    fn rejected_attempts_do_not_track_new_keys() {
+        let limiter = limiter();
+        let start = Instant::now();
+
+        for username in ["alice", "bob", "carol"] {
+            limiter
+                .check_at(&keys(&[&format!("user:{username}"), "ip:a"]), start)
+                .unwrap();
+        }
+
+        assert!(
+            limiter
+                .check_at(&keys(&["user:dave", "ip:a"]), start)
+                .is_err()
+        );
+        assert!(
+            !limiter
+                .entries
+                .lock()
+                .unwrap()
+                .by_key
+                .contains_key("user:dave")
+        );
+    }
+
+    #[test]
+    fn idle_keys_are_swept_once_per_window() {
+        let limiter = limiter();
+        let start = Instant::now();
+
+        limiter.check_at(&keys(&["user:alice"]), start).unwrap();
+        limiter
+            .check_at(&keys(&["user:bob"]), start + WINDOW / 2)
+            .unwrap();
+        assert_eq!(limiter.entries.lock().unwrap().by_key.len(), 2);
+
+        // Alice's attempt has left the window by the sweep, Bob's has not.
+        limiter
+            .check_at(&keys(&["user:carol"]), start + WINDOW)
+            .unwrap();
+
+        let entries = limiter.entries.lock().unwrap();
+        let mut swept = entries.by_key.keys().collect::<Vec<_>>();
+        swept.sort();
+        assert_eq!(swept, ["user:bob", "user:carol"]);
+    }
+}
@@ -0,0 +1,98 @@
+use std::io::Cursor;
+
+use clap::ValueEnum;
+use image::{
+    DynamicImage, ImageFormat, ImageResult, codecs::jpeg::JpegEncoder, imageops::FilterType,
+};
+
+/// How the renditions of stills are encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RenditionEncoding {
+    /// WebP, which keeps every pixel.
+    Lossless,
+    /// JPEG at the rendition quality, or lossless WebP for images with
+    /// transparency, which JPEG cannot store.
+    Lossy,
+}
+
+/// Which renditions are generated for each upload.
+#[derive(Debug, Clone)]
+pub struct RenditionRules {
+    pub widths: Vec<u32>,
+    pub encoding: RenditionEncoding,
+    pub quality: u8,
+}
+
+/// A normalized copy of an image at one size, ready to be stored.
+pub struct EncodedRendition {
+    pub width: u32,
+    pub height: u32,
+    pub extension: &'static str,
+    pub bytes: Vec<u8>,
+}
+
+impl RenditionRules {
+    /// Encodes the image at full size, then at each configured width smaller
+    /// than it, keeping the aspect ratio.
+    pub fn render(&self, image: &DynamicImage) -> ImageResult<Vec<EncodedRendition>> {
+        let mut widths = self
+            .widths
+            .iter()
+            .copied()
+            .filter(|width| *width < image.width())
+            .collect::<Vec<_>>();
+        widths.sort_unstable_by(|a, b| b.cmp(a));
+        widths.dedup();
+
+        let mut renditions = vec![self.encode(image)?];
+
+        for width in widths {
+            let height = ((image.height() as f64 * width as f64 / image.width() as f64).round()
+                as u32)
+                .max(1);
+
+            let resized = image.resize_exact(width, height, FilterType::Lanczos3);
+
+            renditions.push(self.encode(&resized)?);
+        }
+
+        Ok(renditions)
+    }
+
+    fn encode(&self, image: &DynamicImage) -> ImageResult<EncodedRendition> {
+        let mut buf = Cursor::new(Vec::new());
+
+        let extension = if self.encoding == RenditionEncoding::Lossy && is_opaque(image) {
+            image
+                .to_rgb8()
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut buf, self.quality))?;
+
+            "jpg"
+        } else {
+            image.to_rgba8().write_to(&mut buf, ImageFormat::WebP)?;
+
+            "webp"
+        };
+
+        Ok(EncodedRendition {
+            width: image.width(),
+            height: image.height(),
+            extension,
+            bytes: buf.into_inner(),
+        })
+    }
+}
+
+fn is_opaque(image: &DynamicImage) -> bool {
+    !image.color().has_alpha() || image.to_rgba8().pixels().all(|pixel| pixel.0[3] == u8::MAX)
+}
+
+/// The storage key of the untouched upload of an image.
+pub fn original_key(id: &str, extension: &str) -> String {
+    format!("{id}.{extension}")
+}
+
+/// The storage key of the rendition of an image at `width`.
+pub fn rendition_key(id: &str, width: u32, extension: &str) -> String {
+    format!("{id}-{width}.{extension}")
+}
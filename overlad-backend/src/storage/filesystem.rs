@@ -0,0 +1,46 @@
+use std::{io, path::PathBuf};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::storage::Storage;
+
+/// Stores files in a local directory, which is created on the first write.
+pub struct FilesystemStorage {
+    root: PathBuf,
+}
+
+impl FilesystemStorage {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+}
+
+#[async_trait]
+impl Storage for FilesystemStorage {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> io::Result<()> {
+        tokio::fs::create_dir_all(&self.root).await?;
+        tokio::fs::write(self.root.join(key), bytes).await
+    }
+
+    async fn get(&self, key: &str) -> io::Result<Vec<u8>> {
+        tokio::fs::read(self.root.join(key)).await
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        match tokio::fs::remove_file(self.root.join(key)).await {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+            _ => Ok(()),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> io::Result<bool> {
+        tokio::fs::try_exists(self.root.join(key)).await
+    }
+
+    async fn last_modified(&self, key: &str) -> io::Result<DateTime<Utc>> {
+        let metadata = tokio::fs::metadata(self.root.join(key)).await?;
+
+        Ok(DateTime::from(metadata.modified()?))
+    }
+}
@@ -0,0 +1,26 @@
+use std::io;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+pub mod filesystem;
+pub mod s3;
+
+/// Where image files are kept, addressed by flat keys such as `{id}.webp`.
+///
+/// Missing files are reported as [`io::ErrorKind::NotFound`].
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> io::Result<()>;
+
+    async fn get(&self, key: &str) -> io::Result<Vec<u8>>;
+
+    /// Deletes a file, succeeding if it is already gone.
+    async fn delete(&self, key: &str) -> io::Result<()>;
+
+    async fn exists(&self, key: &str) -> io::Result<bool>;
+
+    /// When a file was last written, used to date images stored before upload
+    /// times were recorded.
+    async fn last_modified(&self, key: &str) -> io::Result<DateTime<Utc>>;
+}
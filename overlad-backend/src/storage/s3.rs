@@ -0,0 +1,174 @@
+use std::{io, time::Duration};
+
+use async_trait::async_trait;
+use axum::http::Uri;
+use chrono::{DateTime, Utc};
+use object_store::{
+    ClientOptions, ObjectStore, PutPayload,
+    aws::{AmazonS3, AmazonS3Builder},
+    path::Path,
+};
+
+use crate::storage::Storage;
+
+/// How long connecting to the service may take.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a whole request may take, including reading the object.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Stores files as objects in a bucket of an S3-compatible service, such as
+/// MinIO, so several backends can share them.
+///
+/// Requests use path-style addressing over HTTP or HTTPS, reuse connections,
+/// time out and are retried on transient failures.
+pub struct S3Storage {
+    store: AmazonS3,
+}
+
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+impl S3Storage {
+    pub fn new(config: S3Config) -> Result<Self, String> {
+        let endpoint = config
+            .endpoint
+            .parse::<Uri>()
+            .map_err(|error| format!("bad s3 endpoint: {error}"))?;
+
+        let allow_http = match endpoint.scheme_str() {
+            Some("http") => true,
+            Some("https") => false,
+            _ => {
+                return Err(String::from(
+                    "s3 endpoint must be an http:// or https:// URL",
+                ));
+            }
+        };
+
+        if endpoint.host().is_none() {
+            return Err(String::from("s3 endpoint has no host"));
+        }
+
+        let store = AmazonS3Builder::new()
+            .with_endpoint(config.endpoint)
+            .with_bucket_name(config.bucket)
+            .with_region(config.region)
+            .with_access_key_id(config.access_key_id)
+            .with_secret_access_key(config.secret_access_key)
+            .with_client_options(
+                ClientOptions::new()
+                    .with_allow_http(allow_http)
+                    .with_connect_timeout(CONNECT_TIMEOUT)
+                    .with_timeout(REQUEST_TIMEOUT),
+            )
+            .build()
+            .map_err(|error| format!("bad s3 configuration: {error}"))?;
+
+        Ok(Self { store })
+    }
+}
+
+#[async_trait]
+impl Storage for S3Storage {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> io::Result<()> {
+        self.store
+            .put(&Path::from(key), PutPayload::from(bytes))
+            .await
+            .map_err(to_io_error)?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> io::Result<Vec<u8>> {
+        let result = self
+            .store
+            .get(&Path::from(key))
+            .await
+            .map_err(to_io_error)?;
+
+        Ok(result.bytes().await.map_err(to_io_error)?.to_vec())
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        match self.store.delete(&Path::from(key)).await {
+            Err(object_store::Error::NotFound { .. }) | Ok(()) => Ok(()),
+            Err(error) => Err(to_io_error(error)),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> io::Result<bool> {
+        match self.store.head(&Path::from(key)).await {
+            Ok(_) => Ok(true),
+            Err(object_store::Error::NotFound { .. }) => Ok(false),
+            Err(error) => Err(to_io_error(error)),
+        }
+    }
+
+    async fn last_modified(&self, key: &str) -> io::Result<DateTime<Utc>> {
+        let meta = self
+            .store
+            .head(&Path::from(key))
+            .await
+            .map_err(to_io_error)?;
+
+        Ok(meta.last_modified)
+    }
+}
+
+/// Keeps missing objects distinguishable, as [`Storage`] promises.
+fn to_io_error(error: object_store::Error) -> io::Error {
+    match error {
+        object_store::Error::NotFound { .. } => io::Error::new(io::ErrorKind::NotFound, error),
+        error => io::Error::other(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(endpoint: &str) -> S3Config {
+        S3Config {
+            endpoint: endpoint.to_owned(),
+            bucket: String::from("images"),
+            region: String::from("us-east-1"),
+            access_key_id: String::from("access"),
+            secret_access_key: String::from("secret"),
+        }
+    }
+
+    #[test]
+    fn http_and_https_endpoints_are_accepted() {
+        for endpoint in ["http://localhost:9000", "https://s3.example.com"] {
+            assert!(S3Storage::new(config(endpoint)).is_ok(), "{endpoint}");
+        }
+    }
+
+    #[test]
+    fn other_endpoints_are_rejected() {
+        for endpoint in ["localhost:9000", "ftp://localhost", "http://", "not a url"] {
+            assert!(S3Storage::new(config(endpoint)).is_err(), "{endpoint}");
+        }
+    }
+
+    #[test]
+    fn missing_objects_are_not_found() {
+        let error = object_store::Error::NotFound {
+            path: String::from("a.webp"),
+            source: "missing".into(),
+        };
+
+        assert_eq!(to_io_error(error).kind(), io::ErrorKind::NotFound);
+        assert_eq!(
+            to_io_error(object_store::Error::NotImplemented).kind(),
+            io::ErrorKind::Other
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use std::io::Cursor;
+
+use image::{
+    DynamicImage, ImageDecoder, ImageFormat, ImageReader, ImageResult, Limits,
+    metadata::Orientation,
+};
+use overlad_lib::animation::{AnimationLimits, is_animated};
+
+use crate::error::{Error, Result};
+
+/// Limits on uploaded images, checked against the encoded size and the
+/// dimensions in the header before anything is fully decoded.
+#[derive(Debug, Clone)]
+pub struct UploadLimits {
+    pub max_bytes: usize,
+    pub max_width: u32,
+    pub max_height: u32,
+    pub max_pixels: u64,
+    pub max_frames: usize,
+
+    /// Most pixels across all frames of an animation, which are decoded onto
+    /// the full canvas.
+    pub max_animation_pixels: u64,
+    pub formats: Vec<ImageFormat>,
+}
+
+/// Bytes of the widest pixels decoders produce for the usual formats, 16-bit
+/// RGBA, which bounds a single allocation to [`UploadLimits::max_pixels`] of
+/// them.
+const MAX_BYTES_PER_PIXEL: u64 = 8;
+
+/// An upload that passed [`UploadLimits::check`].
+pub struct CheckedUpload {
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+    pub animated: bool,
+}
+
+impl UploadLimits {
+    /// Checks the size, format and dimensions of an upload, then decodes any
+    /// animation frames under the same limits.
+    pub fn check(&self, bytes: &[u8]) -> Result<CheckedUpload> {
+        if bytes.len() > self.max_bytes {
+            return Err(self.too_many_bytes());
+        }
+
+        let format = image::guess_format(bytes)
+            .map_err(|_| Error::UnsupportedMediaType(String::from("file is not an image")))?;
+
+        if !self.formats.contains(&format) {
+            return Err(Error::UnsupportedMediaType(format!(
+                "{} images are not accepted, upload one of {}",
+                format_name(format),
+                self.formats
+                    .iter()
+                    .map(|format| format_name(*format))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+
+        let (width, height) =
+            ImageReader::with_format(Cursor::new(bytes), format).into_dimensions()?;
+
+        if width > self.max_width || height > self.max_height {
+            return Err(Error::PayloadTooLarge(format!(
+                "image is {width}×{height} pixels, larger than the maximum of {}×{}",
+                self.max_width, self.max_height
+            )));
+        }
+
+        if u64::from(width) * u64::from(height) > self.max_pixels {
+            return Err(Error::PayloadTooLarge(format!(
+                "image has {} pixels, more than the maximum of {}",
+                u64::from(width) * u64::from(height),
+                self.max_pixels
+            )));
+        }
+
+        let animated = is_animated(bytes, &self.animation_limits())?;
+
+        Ok(CheckedUpload {
+            format,
+            width,
+            height,
+            animated,
+        })
+    }
+
+    /// Decodes a checked or stored still image under the same limits, along
+    /// with the orientation from its EXIF data.
+    pub fn decode(
+        &self,
+        bytes: &[u8],
+        format: ImageFormat,
+    ) -> ImageResult<(DynamicImage, Orientation)> {
+        let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+        reader.limits(self.limits());
+
+        let mut decoder = reader.into_decoder()?;
+        let orientation = decoder.orientation()?;
+
+        Ok((DynamicImage::from_decoder(decoder)?, orientation))
+    }
+
+    /// The error for a body or field over [`Self::max_bytes`].
+    pub fn too_many_bytes(&self) -> Error {
+        Error::PayloadTooLarge(format!(
+            "image is larger than the maximum of {} bytes",
+            self.max_bytes
+        ))
+    }
+
+    /// The limits animations are decoded under, on upload and when
+    /// rendering. Rendering holds the resized frames to
+    /// [`UploadLimits::max_animation_pixels`] as well.
+    pub fn animation_limits(&self) -> AnimationLimits {
+        AnimationLimits {
+            decoder: self.limits(),
+            max_frames: self.max_frames,
+            max_total_pixels: self.max_animation_pixels,
+        }
+    }
+
+    fn limits(&self) -> Limits {
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(self.max_width);
+        limits.max_image_height = Some(self.max_height);
+        limits.max_alloc = Some(self.max_pixels.saturating_mul(MAX_BYTES_PER_PIXEL));
+
+        limits
+    }
+}
+
+/// Parses an image format from its name or a file extension, for the
+/// command line.
+pub fn parse_image_format(name: &str) -> Result<ImageFormat, String> {
+    ImageFormat::from_extension(name).ok_or_else(|| format!("unknown image format {name}"))
+}
+
+fn format_name(format: ImageFormat) -> &'static str {
+    format.extensions_str()[0]
+}
@@ -1,11 +1,17 @@
-use std::error::Error;
-
-use axum::http::StatusCode;
-
-pub fn internal_server_error(error: impl Error) -> (StatusCode, String) {
-    (StatusCode::INTERNAL_SERVER_ERROR, format!("{error}"))
-}
+use crate::error::{Error, Result};
 
 pub fn to_row_not_found<T>(maybe: Option<T>) -> sqlx::Result<T> {
     maybe.ok_or(sqlx::Error::RowNotFound)
 }
+
+/// Runs CPU-heavy work such as processing an image or hashing a password on
+/// the blocking thread pool, so it does not stall other requests.
+pub async fn spawn_blocking<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(Error::internal)?
+}
@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+
+use crate::error::{Error, Result};
+
+/// Limits on usernames and passwords, checked on registration and password
+/// changes.
+#[derive(Debug, Clone)]
+pub struct ValidationRules {
+    pub min_username_length: usize,
+    pub max_username_length: usize,
+    pub min_password_length: usize,
+    pub max_password_length: usize,
+}
+
+impl ValidationRules {
+    pub fn validate_username(&self, username: &str) -> Option<String> {
+        let length = username.chars().count();
+
+        if length < self.min_username_length || length > self.max_username_length {
+            Some(format!(
+                "username must be between {} and {} characters",
+                self.min_username_length, self.max_username_length
+            ))
+        } else if username
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control())
+        {
+            Some(String::from("username must not contain spaces or control characters"))
+        } else {
+            None
+        }
+    }
+
+    pub fn validate_password(&self, password: &str) -> Option<String> {
+        let length = password.chars().count();
+
+        if length < self.min_password_length {
+            Some(format!(
+                "password must be at least {} characters",
+                self.min_password_length
+            ))
+        } else if length > self.max_password_length {
+            Some(format!(
+                "password must be at most {} characters",
+                self.max_password_length
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// Collects the problems with a request's fields into a single error.
+#[derive(Default)]
+pub struct FieldErrors(BTreeMap<String, String>);
+
+impl FieldErrors {
+    pub fn check(&mut self, field: &str, maybe_message: Option<String>) {
+        if let Some(message) = maybe_message {
+            self.0.entry(field.to_owned()).or_insert(message);
+        }
+    }
+
+    /// Fails with every collected problem, or succeeds if there were none.
+    pub fn finish(self) -> Result<()> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Validation(self.0))
+        }
+    }
+}
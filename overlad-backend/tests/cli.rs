@@ -0,0 +1,86 @@
+//! Options are read from the command line, the environment and the config
+//! file whether they come before or after a subcommand.
+
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+/// Runs `config check` with `args` in an empty directory and environment, so
+/// neither a `.env` nor the caller's variables leak in.
+fn config_check(dir: &TempDir, envs: &[(&str, &str)], args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_overlad-backend"))
+        .current_dir(dir.path())
+        .env_clear()
+        .envs(envs.iter().copied())
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+const REQUIRED: [&str; 4] = ["--database-url", "sqlite://overlad.db", "--key", "secret"];
+
+#[test]
+fn options_are_accepted_before_the_subcommand() {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(dir.path().join("s3.toml"), "max_text_scale = 3\n").unwrap();
+
+    let args = [
+        &REQUIRED[..],
+        &["--config", "s3.toml", "--port", "6000", "config", "check"],
+    ]
+    .concat();
+    let output = config_check(&dir, &[], &args);
+
+    assert!(output.status.success(), "{output:?}");
+    let stdout = stdout(&output);
+    assert!(stdout.contains("# config file: s3.toml"), "{stdout}");
+    assert!(stdout.contains("port = 6000 # command line"), "{stdout}");
+    assert!(
+        stdout.contains("max_text_scale = 3 # config file"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn options_are_accepted_after_the_subcommand() {
+    let dir = TempDir::new().unwrap();
+
+    let args = [&["config", "check", "--port", "6000"], &REQUIRED[..]].concat();
+    let output = config_check(&dir, &[], &args);
+
+    assert!(output.status.success(), "{output:?}");
+    assert!(stdout(&output).contains("port = 6000 # command line"));
+}
+
+#[test]
+fn options_are_read_from_the_environment() {
+    let dir = TempDir::new().unwrap();
+
+    let output = config_check(
+        &dir,
+        &[
+            ("DATABASE_URL", "sqlite://overlad.db"),
+            ("KEY", "secret"),
+            ("PORT", "6000"),
+            ("FONTS", "typefaces"),
+            ("MAX_OUTPUT_DIMENSION", "2048"),
+            ("MAX_TEXT_SCALE", "4"),
+        ],
+        &["config", "check"],
+    );
+
+    assert!(output.status.success(), "{output:?}");
+    let stdout = stdout(&output);
+
+    for line in [
+        "fonts = \"typefaces\" # environment",
+        "max_output_dimension = 2048 # environment",
+        "max_text_scale = 4 # environment",
+    ] {
+        assert!(stdout.contains(line), "{line} in {stdout}");
+    }
+}
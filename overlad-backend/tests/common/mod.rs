@@ -0,0 +1,304 @@
+//! Builds the router around a scratch SQLite database and image directory, and
+//! sends it requests without a server.
+
+#![allow(dead_code)]
+
+use std::{io::Cursor, sync::Arc, time::Duration};
+
+use axum::{
+    Router,
+    body::{Body, Bytes},
+    http::{HeaderMap, Method, Request, StatusCode, header},
+};
+use hmac::{Hmac, Mac};
+use http_body_util::BodyExt;
+use image::{Delay, Frame, ImageFormat, Rgba, RgbaImage};
+use overlad_api::{DEFAULT_FONT, ErrorResponse, TokenResponse};
+use overlad_backend::{
+    AppState,
+    cache::OverlayCache,
+    cors::CorsRules,
+    db,
+    rate_limit::{RateLimitRules, RateLimiter},
+    rendition::{RenditionEncoding, RenditionRules},
+    router,
+    storage::filesystem::FilesystemStorage,
+    upload_limits::UploadLimits,
+    validation::ValidationRules,
+};
+use overlad_lib::{animation::encode_gif, font::FontRegistry};
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+pub const PASSWORD: &str = "correct horse";
+
+pub struct TestApp {
+    pub state: AppState,
+    router: Router,
+    // Removed on drop, so kept for as long as the app.
+    _dir: TempDir,
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl TestResponse {
+    pub fn json<T: DeserializeOwned>(&self) -> T {
+        serde_json::from_slice(&self.body).unwrap_or_else(|error| {
+            panic!(
+                "{error} in body {:?} of a {} response",
+                String::from_utf8_lossy(&self.body),
+                self.status
+            )
+        })
+    }
+
+    /// Checks that the response is an [`ErrorResponse`] with `status`.
+    pub fn error(&self, status: StatusCode) -> ErrorResponse {
+        assert_eq!(
+            self.status,
+            status,
+            "unexpected status with body {:?}",
+            String::from_utf8_lossy(&self.body)
+        );
+        assert_eq!(
+            self.headers.get(header::CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+
+        self.json()
+    }
+}
+
+impl TestApp {
+    pub async fn new() -> Self {
+        Self::with_state(|_| {}).await
+    }
+
+    /// Builds the app after `configure` has changed its default state.
+    pub async fn with_state(configure: impl FnOnce(&mut AppState)) -> Self {
+        let dir = TempDir::new().unwrap();
+
+        let db = db::connect(&format!(
+            "sqlite://{}",
+            dir.path().join("overlad.db").display()
+        ))
+        .await
+        .unwrap();
+        db.migrate_up().await.unwrap();
+
+        let mut fonts = FontRegistry::new();
+        fonts
+            .insert(
+                DEFAULT_FONT,
+                include_bytes!("../../../roboto.ttf").to_vec(),
+                "ttf",
+            )
+            .unwrap();
+
+        let mut state = AppState {
+            key: Hmac::new_from_slice(b"test key").unwrap(),
+            db,
+            storage: Arc::new(FilesystemStorage::new(dir.path().join("images"))),
+            fonts: Arc::new(fonts),
+            overlay_cache: Arc::new(OverlayCache::new(64, 16 * 1024 * 1024)),
+            overlay_max_age: 60,
+            max_output_dimension: 1024,
+            max_text_scale: 10.0,
+            upload_limits: UploadLimits {
+                max_bytes: 1_000_000,
+                max_width: 2048,
+                max_height: 2048,
+                max_pixels: 2048 * 2048,
+                max_frames: 16,
+                max_animation_pixels: 64 * 64 * 16,
+                formats: vec![
+                    ImageFormat::Png,
+                    ImageFormat::Jpeg,
+                    ImageFormat::Gif,
+                    ImageFormat::WebP,
+                ],
+            },
+            rendition_rules: RenditionRules {
+                widths: vec![64],
+                encoding: RenditionEncoding::Lossless,
+                quality: 85,
+            },
+            access_token_ttl: 60,
+            refresh_token_ttl: 60,
+            validation_rules: ValidationRules {
+                min_username_length: 3,
+                max_username_length: 32,
+                min_password_length: 8,
+                max_password_length: 256,
+            },
+            auth_rate_limiter: Arc::new(RateLimiter::new(RateLimitRules {
+                max_attempts: 100,
+                window: Duration::from_secs(60),
+                max_failures: 100,
+                lockout: Duration::from_secs(60),
+            })),
+            forwarded_header: None,
+            cors_rules: CorsRules {
+                origins: vec!["http://localhost:3000".parse().unwrap()],
+                methods: vec![Method::GET, Method::POST, Method::DELETE],
+                headers: vec![header::AUTHORIZATION, header::CONTENT_TYPE],
+            },
+        };
+
+        configure(&mut state);
+
+        Self {
+            router: router(state.clone()),
+            state,
+            _dir: dir,
+        }
+    }
+
+    pub async fn request(&self, request: Request<Body>) -> TestResponse {
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let (parts, body) = response.into_parts();
+
+        TestResponse {
+            status: parts.status,
+            headers: parts.headers,
+            body: body.collect().await.unwrap().to_bytes(),
+        }
+    }
+
+    pub async fn get(&self, uri: &str, token: Option<&str>) -> TestResponse {
+        self.request(
+            authorized(Request::get(uri), token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+    }
+
+    pub async fn delete(&self, uri: &str, token: Option<&str>) -> TestResponse {
+        self.request(
+            authorized(Request::delete(uri), token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+    }
+
+    pub async fn post_json(&self, uri: &str, token: Option<&str>, body: Value) -> TestResponse {
+        self.request(
+            authorized(Request::post(uri), token)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+    }
+
+    /// Registers `username` with [`PASSWORD`] and logs in.
+    pub async fn register(&self, username: &str) -> TokenResponse {
+        let response = self
+            .post_json(
+                "/register",
+                None,
+                json!({
+                    "username": username,
+                    "password": PASSWORD,
+                    "confirm_password": PASSWORD,
+                }),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::OK);
+
+        let response = self
+            .post_json(
+                "/token",
+                None,
+                json!({ "username": username, "password": PASSWORD }),
+            )
+            .await;
+        assert_eq!(response.status, StatusCode::OK);
+
+        response.json()
+    }
+
+    /// Uploads `bytes` as a multipart form with the extra text `fields`.
+    pub async fn upload(&self, token: &str, bytes: &[u8], fields: &[(&str, &str)]) -> TestResponse {
+        const BOUNDARY: &str = "overlad-test-boundary";
+
+        let mut body = Vec::new();
+
+        for (name, value) in fields {
+            body.extend_from_slice(
+                format!(
+                    "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+                )
+                .as_bytes(),
+            );
+        }
+
+        body.extend_from_slice(
+            format!(
+                "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"image\"; filename=\"image\"\r\nContent-Type: application/octet-stream\r\n\r\n"
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(bytes);
+        body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+
+        self.request(
+            authorized(Request::post("/upload"), Some(token))
+                .header(
+                    header::CONTENT_TYPE,
+                    format!("multipart/form-data; boundary={BOUNDARY}"),
+                )
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+    }
+}
+
+fn authorized(
+    builder: axum::http::request::Builder,
+    token: Option<&str>,
+) -> axum::http::request::Builder {
+    match token {
+        Some(token) => builder.header(header::AUTHORIZATION, format!("Bearer {token}")),
+        None => builder,
+    }
+}
+
+/// Encodes a `width` by `height` image with a gradient, so its pixels differ.
+pub fn png(width: u32, height: u32) -> Vec<u8> {
+    let image = RgbaImage::from_fn(width, height, |x, y| {
+        Rgba([(x % 256) as u8, (y % 256) as u8, 128, 255])
+    });
+
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .unwrap();
+
+    bytes
+}
+
+/// Encodes an animated GIF of `frame_count` solid `width` by `height` frames.
+pub fn gif(width: u32, height: u32, frame_count: u8) -> Vec<u8> {
+    let frames = (0..frame_count)
+        .map(|index| {
+            Frame::from_parts(
+                RgbaImage::from_pixel(width, height, Rgba([index, 0, 0, 255])),
+                0,
+                0,
+                Delay::from_numer_denom_ms(100, 1),
+            )
+        })
+        .collect();
+
+    encode_gif(frames).unwrap()
+}
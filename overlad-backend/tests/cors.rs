@@ -0,0 +1,135 @@
+use axum::{
+    body::Body,
+    http::{
+        Method, Request, StatusCode,
+        header::{
+            ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+            ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_REQUEST_HEADERS,
+            ACCESS_CONTROL_REQUEST_METHOD, ORIGIN,
+        },
+    },
+};
+
+use common::{TestApp, TestResponse};
+
+mod common;
+
+const ALLOWED_ORIGIN: &str = "http://localhost:3000";
+const OTHER_ORIGIN: &str = "https://evil.example";
+
+async fn preflight(app: &TestApp, uri: &str, origin: &str, method: Method) -> TestResponse {
+    app.request(
+        Request::options(uri)
+            .header(ORIGIN, origin)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, method.as_str())
+            .header(ACCESS_CONTROL_REQUEST_HEADERS, "authorization,content-type")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn preflights_from_allowed_origins_are_echoed() {
+    let app = TestApp::new().await;
+
+    for (uri, method) in [
+        ("/token", Method::POST),
+        ("/me", Method::GET),
+        ("/image/some-id", Method::DELETE),
+    ] {
+        let response = preflight(&app, uri, ALLOWED_ORIGIN, method).await;
+
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(
+            response.headers.get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            ALLOWED_ORIGIN
+        );
+
+        let allowed_methods = response
+            .headers
+            .get(ACCESS_CONTROL_ALLOW_METHODS)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert!(allowed_methods.contains("DELETE"), "{allowed_methods}");
+
+        let allowed_headers = response
+            .headers
+            .get(ACCESS_CONTROL_ALLOW_HEADERS)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert!(
+            allowed_headers.contains("authorization"),
+            "{allowed_headers}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn preflights_from_other_origins_are_not_allowed() {
+    let app = TestApp::new().await;
+
+    let response = preflight(&app, "/token", OTHER_ORIGIN, Method::POST).await;
+
+    assert!(!response.headers.contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+}
+
+#[tokio::test]
+async fn responses_to_other_origins_are_not_allowed() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .request(
+            Request::get("/fonts")
+                .header(ORIGIN, OTHER_ORIGIN)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(!response.headers.contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+}
+
+#[tokio::test]
+async fn any_origin_may_be_allowed() {
+    let app = TestApp::with_state(|state| {
+        state.cors_rules.origins = vec!["*".parse().unwrap()];
+    })
+    .await;
+
+    let response = preflight(&app, "/token", OTHER_ORIGIN, Method::POST).await;
+
+    assert_eq!(
+        response.headers.get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+        "*"
+    );
+}
+
+#[tokio::test]
+async fn overlays_are_public() {
+    let app = TestApp::new().await;
+
+    let response = preflight(&app, "/overlay/some-id", OTHER_ORIGIN, Method::GET).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(
+        response.headers.get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+        "*"
+    );
+
+    let response = app
+        .request(
+            Request::get("/overlay/some-id")
+                .header(ORIGIN, OTHER_ORIGIN)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+    response.error(StatusCode::NOT_FOUND);
+    assert_eq!(
+        response.headers.get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+        "*"
+    );
+}
@@ -0,0 +1,393 @@
+//! Every documented error is sent as an `ErrorResponse`, including requests
+//! rejected before reaching a handler.
+
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode, header},
+};
+use overlad_api::Image;
+use overlad_backend::rate_limit::{RateLimitRules, RateLimiter};
+use serde_json::json;
+
+use common::{PASSWORD, TestApp, gif, png};
+
+mod common;
+
+#[tokio::test]
+async fn malformed_json_is_a_bad_request() {
+    let app = TestApp::new().await;
+
+    for uri in ["/register", "/token", "/token/refresh", "/logout"] {
+        let response = app
+            .request(
+                Request::post(uri)
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from("{"))
+                    .unwrap(),
+            )
+            .await;
+
+        response.error(StatusCode::BAD_REQUEST);
+    }
+}
+
+#[tokio::test]
+async fn json_of_the_wrong_shape_is_a_bad_request() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_json("/token", None, json!({ "username": "alice" }))
+        .await;
+    let error = response.error(StatusCode::BAD_REQUEST);
+    assert!(error.error.contains("password"), "{}", error.error);
+
+    let response = app
+        .post_json("/register", None, json!({ "username": 1 }))
+        .await;
+    response.error(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn json_without_a_content_type_is_unsupported() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .request(
+            Request::post("/token")
+                .body(Body::from(r#"{"username":"alice","password":"secret"}"#))
+                .unwrap(),
+        )
+        .await;
+
+    response.error(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+#[tokio::test]
+async fn change_password_rejects_bad_json() {
+    let app = TestApp::new().await;
+    let tokens = app.register("alice").await;
+
+    let response = app
+        .post_json(
+            "/me/password",
+            Some(&tokens.token),
+            json!({ "old_password": PASSWORD }),
+        )
+        .await;
+
+    response.error(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn register_validates_fields() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_json(
+            "/register",
+            None,
+            json!({ "username": "a b", "password": "short", "confirm_password": "other" }),
+        )
+        .await;
+    let error = response.error(StatusCode::UNPROCESSABLE_ENTITY);
+
+    assert_eq!(
+        error.fields.keys().collect::<Vec<_>>(),
+        ["confirm_password", "password", "username"]
+    );
+}
+
+#[tokio::test]
+async fn register_rejects_taken_usernames() {
+    let app = TestApp::new().await;
+    app.register("alice").await;
+
+    let response = app
+        .post_json(
+            "/register",
+            None,
+            json!({ "username": "alice", "password": PASSWORD, "confirm_password": PASSWORD }),
+        )
+        .await;
+
+    response.error(StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn concurrent_registrations_of_a_username_conflict() {
+    let app = TestApp::new().await;
+    let register = || {
+        app.post_json(
+            "/register",
+            None,
+            json!({ "username": "alice", "password": PASSWORD, "confirm_password": PASSWORD }),
+        )
+    };
+
+    let responses = tokio::join!(register(), register(), register(), register());
+    let mut statuses = [responses.0, responses.1, responses.2, responses.3]
+        .map(|response| response.status)
+        .to_vec();
+    statuses.sort();
+
+    assert_eq!(
+        statuses,
+        [
+            StatusCode::OK,
+            StatusCode::CONFLICT,
+            StatusCode::CONFLICT,
+            StatusCode::CONFLICT
+        ]
+    );
+}
+
+#[tokio::test]
+async fn token_rejects_wrong_passwords() {
+    let app = TestApp::new().await;
+    app.register("alice").await;
+
+    for username in ["alice", "bob"] {
+        let response = app
+            .post_json(
+                "/token",
+                None,
+                json!({ "username": username, "password": "wrong password" }),
+            )
+            .await;
+
+        response.error(StatusCode::UNAUTHORIZED);
+    }
+}
+
+#[tokio::test]
+async fn auth_attempts_are_rate_limited() {
+    let app = TestApp::with_state(|state| {
+        state.auth_rate_limiter = Arc::new(RateLimiter::new(RateLimitRules {
+            max_attempts: 2,
+            window: Duration::from_secs(60),
+            max_failures: 100,
+            lockout: Duration::from_secs(60),
+        }));
+    })
+    .await;
+
+    let login = || {
+        app.post_json(
+            "/token",
+            None,
+            json!({ "username": "alice", "password": "x" }),
+        )
+    };
+    login().await.error(StatusCode::UNAUTHORIZED);
+    login().await.error(StatusCode::UNAUTHORIZED);
+
+    let response = login().await;
+    response.error(StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers.contains_key(header::RETRY_AFTER));
+}
+
+#[tokio::test]
+async fn refresh_tokens_are_single_use() {
+    let app = TestApp::new().await;
+    let tokens = app.register("alice").await;
+
+    let refresh = || {
+        app.post_json(
+            "/token/refresh",
+            None,
+            json!({ "refresh_token": tokens.refresh_token }),
+        )
+    };
+
+    assert_eq!(refresh().await.status, StatusCode::OK);
+    refresh().await.error(StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn authenticated_routes_require_a_valid_token() {
+    let app = TestApp::new().await;
+
+    app.get("/me", None).await.error(StatusCode::UNAUTHORIZED);
+    app.get("/me", Some("not a token"))
+        .await
+        .error(StatusCode::UNAUTHORIZED);
+    app.upload("not a token", &png(4, 4), &[])
+        .await
+        .error(StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn change_password_checks_the_old_password() {
+    let app = TestApp::new().await;
+    let tokens = app.register("alice").await;
+
+    let response = app
+        .post_json(
+            "/me/password",
+            Some(&tokens.token),
+            json!({
+                "old_password": "wrong password",
+                "new_password": "new password",
+                "confirm_password": "new password",
+            }),
+        )
+        .await;
+    let error = response.error(StatusCode::UNPROCESSABLE_ENTITY);
+
+    assert!(error.fields.contains_key("old_password"));
+}
+
+#[tokio::test]
+async fn upload_rejects_what_is_not_an_image() {
+    let app = TestApp::new().await;
+    let tokens = app.register("alice").await;
+
+    app.upload(&tokens.token, b"not an image", &[])
+        .await
+        .error(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+#[tokio::test]
+async fn upload_rejects_images_over_the_limits() {
+    let app = TestApp::with_state(|state| state.upload_limits.max_width = 16).await;
+    let tokens = app.register("alice").await;
+
+    app.upload(&tokens.token, &png(32, 8), &[])
+        .await
+        .error(StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn upload_rejects_animations_over_the_limits() {
+    let app = TestApp::new().await;
+    let tokens = app.register("alice").await;
+
+    // 17 frames is over the frame limit of 16, and 16 frames of 64×128 over
+    // the budget of 16 frames of 64×64.
+    for bytes in [gif(8, 8, 17), gif(64, 128, 16)] {
+        app.upload(&tokens.token, &bytes, &[])
+            .await
+            .error(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    assert_eq!(
+        app.upload(&tokens.token, &gif(64, 64, 16), &[])
+            .await
+            .status,
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn upload_rejects_bodies_over_the_limit() {
+    let app = TestApp::with_state(|state| state.upload_limits.max_bytes = 1024).await;
+    let tokens = app.register("alice").await;
+
+    app.upload(&tokens.token, &vec![0; 256 * 1024], &[])
+        .await
+        .error(StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn upload_rejects_bad_fields() {
+    let app = TestApp::new().await;
+    let tokens = app.register("alice").await;
+
+    app.upload(&tokens.token, &png(8, 8), &[("rotate", "45")])
+        .await
+        .error(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn missing_resources_are_not_found() {
+    let app = TestApp::new().await;
+
+    for uri in [
+        "/image/missing",
+        "/overlay/missing",
+        "/user/42",
+        "/fonts/missing",
+    ] {
+        app.get(uri, None).await.error(StatusCode::NOT_FOUND);
+    }
+}
+
+#[tokio::test]
+async fn internal_errors_do_not_reveal_their_details() {
+    let app = TestApp::new().await;
+    let tokens = app.register("alice").await;
+    let image = app
+        .upload(&tokens.token, &png(8, 8), &[])
+        .await
+        .json::<Image>();
+
+    for rendition in app.state.db.get_renditions(&image.id).await.unwrap() {
+        app.state.storage.delete(&rendition.key()).await.unwrap();
+    }
+
+    let error = app
+        .get(&format!("/overlay/{}", image.id), None)
+        .await
+        .error(StatusCode::INTERNAL_SERVER_ERROR);
+
+    assert_eq!(error.error, "internal server error");
+    assert!(error.fields.is_empty());
+}
+
+#[tokio::test]
+async fn bad_path_parameters_are_bad_requests() {
+    let app = TestApp::new().await;
+
+    for uri in ["/user/alice", "/user/alice/images"] {
+        app.get(uri, None).await.error(StatusCode::BAD_REQUEST);
+    }
+}
+
+#[tokio::test]
+async fn bad_query_parameters_are_bad_requests() {
+    let app = TestApp::new().await;
+    let tokens = app.register("alice").await;
+    let image = app
+        .upload(&tokens.token, &png(8, 8), &[])
+        .await
+        .json::<Image>();
+
+    for uri in [
+        "/all_images?limit=many".to_owned(),
+        "/all_images?cursor=bad".to_owned(),
+        "/user/1/images?sort=sideways".to_owned(),
+        format!("/overlay/{}?quality=500", image.id),
+        format!("/overlay/{}?format=bmp", image.id),
+        format!("/overlay/{}.bmp", image.id),
+        format!("/overlay/{}?font=missing", image.id),
+        format!("/overlay/{}?text_color=nothex", image.id),
+        format!("/overlay/{}?resize_width=wide", image.id),
+    ] {
+        app.get(&uri, None).await.error(StatusCode::BAD_REQUEST);
+    }
+}
+
+#[tokio::test]
+async fn only_the_owner_may_delete_an_image() {
+    let app = TestApp::new().await;
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let image = app
+        .upload(&alice.token, &png(8, 8), &[])
+        .await
+        .json::<Image>();
+
+    let uri = format!("/image/{}", image.id);
+
+    app.delete(&uri, None).await.error(StatusCode::UNAUTHORIZED);
+    app.delete(&uri, Some(&bob.token))
+        .await
+        .error(StatusCode::FORBIDDEN);
+    assert_eq!(
+        app.delete(&uri, Some(&alice.token)).await.status,
+        StatusCode::NO_CONTENT
+    );
+    app.get(&uri, None).await.error(StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,373 @@
+use std::io::Cursor;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode, header},
+};
+use image::{AnimationDecoder, Delay, Frame, Rgba, RgbaImage, codecs::gif::GifDecoder};
+use overlad_api::Image;
+use overlad_backend::rendition::original_key;
+use overlad_lib::animation::encode_gif;
+
+use common::{TestApp, TestResponse, gif, png};
+
+mod common;
+
+async fn upload(app: &TestApp, width: u32, height: u32) -> Image {
+    let tokens = app.register("alice").await;
+
+    app.upload(&tokens.token, &png(width, height), &[])
+        .await
+        .json()
+}
+
+#[tokio::test]
+async fn resizes_beyond_the_maximum_dimension_are_rejected() {
+    let app = TestApp::new().await;
+    let image = upload(&app, 10, 1000).await;
+
+    for query in [
+        "resize_width=1025",
+        "resize_width=0",
+        "resize_width=1024&upscale=true",
+    ] {
+        let response = app
+            .get(&format!("/overlay/{}?{query}", image.id), None)
+            .await;
+
+        let error = response.error(StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(error.fields.contains_key("resize_width"), "{query}");
+    }
+}
+
+#[tokio::test]
+async fn resizes_within_the_maximum_dimension_are_rendered() {
+    let app = TestApp::new().await;
+    let image = upload(&app, 10, 1000).await;
+
+    for (query, size) in [
+        ("resize_width=5", (5, 500)),
+        (
+            "resize_width=1024&resize_height=10&fit=cover&upscale=true",
+            (1024, 10),
+        ),
+        (
+            "resize_width=1024&resize_height=1024&fit=contain",
+            (1024, 1024),
+        ),
+    ] {
+        let response = app
+            .get(&format!("/overlay/{}.png?{query}", image.id), None)
+            .await;
+        assert_eq!(response.status, StatusCode::OK, "{query}");
+
+        let rendered = image::load_from_memory(&response.body).unwrap();
+        assert_eq!((rendered.width(), rendered.height()), size, "{query}");
+    }
+}
+
+#[tokio::test]
+async fn text_scales_beyond_the_maximum_are_rejected() {
+    let app = TestApp::new().await;
+    let image = upload(&app, 16, 16).await;
+
+    for (query, field) in [
+        ("text_scale=0", "text_scale"),
+        ("text_scale=-1", "text_scale"),
+        ("text_scale=NaN", "text_scale"),
+        ("text_scale=inf", "text_scale"),
+        ("text_scale=10.5", "text_scale"),
+        ("text[1]=b&text_scale[1]=1e9", "text_scale[1]"),
+        ("text_scale=auto&min_text_scale=0", "min_text_scale"),
+        ("text_scale=auto&max_text_scale=11", "max_text_scale"),
+        (
+            "text_scale=auto&min_text_scale=2&max_text_scale=1",
+            "max_text_scale",
+        ),
+    ] {
+        let response = app
+            .get(&format!("/overlay/{}?text=a&{query}", image.id), None)
+            .await;
+
+        let error = response.error(StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error.fields.keys().collect::<Vec<_>>(), [field], "{query}");
+    }
+}
+
+#[tokio::test]
+async fn text_scales_within_the_maximum_are_rendered() {
+    let app = TestApp::new().await;
+    let image = upload(&app, 16, 16).await;
+
+    for query in [
+        "text_scale=10",
+        "text_scale=0.5",
+        "text_scale=auto&max_text_scale=10",
+    ] {
+        let response = app
+            .get(&format!("/overlay/{}?text=a&{query}", image.id), None)
+            .await;
+
+        assert_eq!(response.status, StatusCode::OK, "{query}");
+    }
+}
+
+#[tokio::test]
+async fn deleting_an_image_drops_its_cached_overlays_despite_missing_files() {
+    let app = TestApp::new().await;
+    let tokens = app.register("alice").await;
+    let image = app
+        .upload(&tokens.token, &png(8, 8), &[])
+        .await
+        .json::<Image>();
+    let uri = format!("/overlay/{}", image.id);
+
+    assert_eq!(app.get(&uri, None).await.status, StatusCode::OK);
+
+    app.state
+        .storage
+        .delete(&original_key(&image.id, &image.extension))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        app.delete(&format!("/image/{}", image.id), Some(&tokens.token))
+            .await
+            .status,
+        StatusCode::NO_CONTENT
+    );
+    app.get(&uri, None).await.error(StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn stored_animations_over_the_limits_are_not_rendered() {
+    let app = TestApp::new().await;
+    let tokens = app.register("alice").await;
+    let image = app
+        .upload(&tokens.token, &gif(8, 8, 4), &[])
+        .await
+        .json::<Image>();
+    let uri = format!("/overlay/{}", image.id);
+
+    let response = app.get(&uri, None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers[header::CONTENT_TYPE], "image/gif");
+
+    // Stand in for an animation stored under looser limits.
+    for rendition in app.state.db.get_renditions(&image.id).await.unwrap() {
+        app.state
+            .storage
+            .put(&rendition.key(), gif(8, 8, 17))
+            .await
+            .unwrap();
+    }
+
+    app.get(&format!("{uri}?text=hi"), None)
+        .await
+        .error(StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[tokio::test]
+async fn animations_resized_beyond_the_total_pixels_are_rejected() {
+    let app = TestApp::new().await;
+    let tokens = app.register("alice").await;
+    let image = app
+        .upload(&tokens.token, &gif(8, 8, 16), &[])
+        .await
+        .json::<Image>();
+
+    // 16 frames of 64×64 are exactly the test limit.
+    let response = app
+        .get(
+            &format!("/overlay/{}?resize_width=64&upscale=true", image.id),
+            None,
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    app.get(
+        &format!("/overlay/{}?resize_width=65&upscale=true", image.id),
+        None,
+    )
+    .await
+    .error(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn animations_keep_their_frames_and_delays() {
+    let app = TestApp::new().await;
+    let tokens = app.register("alice").await;
+    let delays = [50, 100, 250];
+    let frames = delays
+        .iter()
+        .enumerate()
+        .map(|(index, delay)| {
+            Frame::from_parts(
+                RgbaImage::from_pixel(16, 16, Rgba([index as u8 * 100, 0, 0, 255])),
+                0,
+                0,
+                Delay::from_numer_denom_ms(*delay, 1),
+            )
+        })
+        .collect();
+    let image = app
+        .upload(&tokens.token, &encode_gif(frames).unwrap(), &[])
+        .await
+        .json::<Image>();
+
+    let response = app
+        .get(
+            &format!("/overlay/{}?text=hi&resize_width=8", image.id),
+            None,
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers[header::CONTENT_TYPE], "image/gif");
+
+    let rendered = GifDecoder::new(Cursor::new(response.body))
+        .unwrap()
+        .into_frames()
+        .collect_frames()
+        .unwrap();
+
+    assert_eq!(
+        rendered
+            .iter()
+            .map(|frame| frame.delay().numer_denom_ms())
+            .collect::<Vec<_>>(),
+        delays.map(|delay| (delay, 1))
+    );
+    for frame in rendered {
+        assert_eq!(frame.buffer().dimensions(), (8, 8));
+    }
+}
+
+#[tokio::test]
+async fn rendition_widths_are_listed() {
+    let app = TestApp::with_state(|state| state.rendition_rules.widths = vec![512, 64, 512]).await;
+
+    let response = app.get("/renditions", None).await;
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json::<Vec<u32>>(), [64, 512]);
+}
+
+#[tokio::test]
+async fn overlays_carry_an_etag_and_cache_control() {
+    let app = TestApp::new().await;
+    let image = upload(&app, 16, 16).await;
+
+    let response = app
+        .get(&format!("/overlay/{}?text=a&text_scale=2", image.id), None)
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(
+        response.headers[header::CACHE_CONTROL],
+        "public, max-age=60"
+    );
+
+    let etag = &response.headers[header::ETAG];
+    assert!(etag.to_str().unwrap().starts_with('"'));
+
+    // Equivalent query strings share the cached rendering.
+    let reordered = app
+        .get(&format!("/overlay/{}?text_scale=2&text=a", image.id), None)
+        .await;
+    assert_eq!(&reordered.headers[header::ETAG], etag);
+    assert_eq!(reordered.body, response.body);
+
+    let other = app
+        .get(&format!("/overlay/{}?text=b", image.id), None)
+        .await;
+    assert_ne!(&other.headers[header::ETAG], etag);
+}
+
+#[tokio::test]
+async fn matching_etags_are_not_modified() {
+    let app = TestApp::new().await;
+    let image = upload(&app, 16, 16).await;
+    let uri = format!("/overlay/{}?text=a", image.id);
+    let etag = app.get(&uri, None).await.headers[header::ETAG]
+        .to_str()
+        .unwrap()
+        .to_owned();
+
+    for (if_none_match, status) in [
+        (etag.clone(), StatusCode::NOT_MODIFIED),
+        (format!("W/{etag}"), StatusCode::NOT_MODIFIED),
+        (format!("\"other\", {etag}"), StatusCode::NOT_MODIFIED),
+        (String::from("\"other\""), StatusCode::OK),
+    ] {
+        let response = app
+            .request(
+                Request::get(&uri)
+                    .header(header::IF_NONE_MATCH, &if_none_match)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+
+        assert_eq!(response.status, status, "{if_none_match}");
+        assert_eq!(response.headers[header::ETAG], etag, "{if_none_match}");
+        assert_eq!(
+            response.headers[header::CACHE_CONTROL],
+            "public, max-age=60",
+            "{if_none_match}"
+        );
+
+        if status == StatusCode::NOT_MODIFIED {
+            assert!(response.body.is_empty(), "{if_none_match}");
+        }
+    }
+}
+
+async fn get_accepting(app: &TestApp, uri: &str, accept: &str) -> TestResponse {
+    app.request(
+        Request::get(uri)
+            .header(header::ACCEPT, accept)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn output_formats_are_negotiated() {
+    let app = TestApp::new().await;
+    let image = upload(&app, 16, 16).await;
+    let uri = format!("/overlay/{}", image.id);
+
+    for (path, accept, content_type) in [
+        (uri.clone(), "image/png", "image/png"),
+        (uri.clone(), "image/png;q=0.5, image/jpeg", "image/jpeg"),
+        (uri.clone(), "*/*", "image/webp"),
+        (uri.clone(), "image/*", "image/webp"),
+        // The query parameter overrides Accept, and the extension both.
+        (format!("{uri}?format=jpeg"), "image/png", "image/jpeg"),
+        (format!("{uri}.gif?format=jpeg"), "image/png", "image/gif"),
+    ] {
+        let response = get_accepting(&app, &path, accept).await;
+
+        assert_eq!(response.status, StatusCode::OK, "{path} {accept}");
+        assert_eq!(
+            response.headers[header::CONTENT_TYPE],
+            content_type,
+            "{path} {accept}"
+        );
+        assert_eq!(response.headers[header::VARY], "accept", "{path} {accept}");
+        image::load_from_memory(&response.body).unwrap();
+    }
+}
+
+#[tokio::test]
+async fn unsupported_output_formats_are_rejected() {
+    let app = TestApp::new().await;
+    let image = upload(&app, 16, 16).await;
+
+    for path in [
+        format!("/overlay/{}.bmp", image.id),
+        format!("/overlay/{}?format=bmp", image.id),
+    ] {
+        app.get(&path, None).await.error(StatusCode::BAD_REQUEST);
+    }
+}
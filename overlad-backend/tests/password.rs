@@ -0,0 +1,82 @@
+use axum::http::StatusCode;
+use overlad_api::TokenResponse;
+use serde_json::json;
+
+use common::{PASSWORD, TestApp};
+
+mod common;
+
+const NEW_PASSWORD: &str = "battery staple";
+
+#[tokio::test]
+async fn changing_the_password_revokes_every_refresh_token() {
+    let app = TestApp::new().await;
+    let first_session = app.register("alice").await;
+    let second_session = app
+        .post_json(
+            "/token",
+            None,
+            json!({ "username": "alice", "password": PASSWORD }),
+        )
+        .await
+        .json::<TokenResponse>();
+
+    let response = app
+        .post_json(
+            "/me/password",
+            Some(&first_session.token),
+            json!({
+                "old_password": PASSWORD,
+                "new_password": NEW_PASSWORD,
+                "confirm_password": NEW_PASSWORD,
+            }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    for session in [&first_session, &second_session] {
+        app.post_json(
+            "/token/refresh",
+            None,
+            json!({ "refresh_token": session.refresh_token }),
+        )
+        .await
+        .error(StatusCode::UNAUTHORIZED);
+    }
+
+    let response = app
+        .post_json(
+            "/token",
+            None,
+            json!({ "username": "alice", "password": NEW_PASSWORD }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn a_rejected_password_change_keeps_refresh_tokens() {
+    let app = TestApp::new().await;
+    let session = app.register("alice").await;
+
+    app.post_json(
+        "/me/password",
+        Some(&session.token),
+        json!({
+            "old_password": "wrong password",
+            "new_password": NEW_PASSWORD,
+            "confirm_password": NEW_PASSWORD,
+        }),
+    )
+    .await
+    .error(StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = app
+        .post_json(
+            "/token/refresh",
+            None,
+            json!({ "refresh_token": session.refresh_token }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+}
@@ -0,0 +1,479 @@
+//! Runs the same checks against every [`Repository`] implementation.
+//!
+//! SQLite runs against a scratch file. Postgres needs a server, so its tests
+//! are ignored unless run with `--ignored` and `TEST_POSTGRES_URL` set, which
+//! creates a scratch database on that server for each test:
+//!
+//! ```sh
+//! TEST_POSTGRES_URL=postgres://postgres@localhost/postgres \
+//!     cargo test -p overlad-backend --test repository postgres -- --ignored
+//! ```
+
+use std::sync::Arc;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use overlad_api::ImageSort;
+use overlad_backend::{
+    db::{
+        self, Repository,
+        image::{ImageCursor, ImageMetadata},
+    },
+    rendition::EncodedRendition,
+};
+use sqlx::{Connection, PgConnection};
+use tempfile::TempDir;
+
+macro_rules! repository_tests {
+    ($($name:ident),* $(,)?) => {
+        mod sqlite {
+            $(
+                #[tokio::test]
+                async fn $name() {
+                    let dir = tempfile::TempDir::new().unwrap();
+                    let repository = super::sqlite(&dir).await;
+
+                    super::$name(&*repository).await;
+                }
+            )*
+        }
+
+        mod postgres {
+            $(
+                #[tokio::test]
+                #[ignore = "needs a Postgres server in TEST_POSTGRES_URL"]
+                async fn $name() {
+                    let database = super::ScratchDatabase::create().await;
+
+                    super::$name(&*database.repository).await;
+
+                    database.drop().await;
+                }
+            )*
+        }
+    };
+}
+
+repository_tests!(
+    migrations_are_all_applied,
+    users,
+    image_pages,
+    renditions,
+    deleting_an_image_deletes_its_renditions,
+    backfill_queries,
+    refresh_tokens,
+    revoking_every_refresh_token_of_a_user,
+    updating_a_password_revokes_refresh_tokens,
+);
+
+async fn sqlite(dir: &TempDir) -> Arc<dyn Repository> {
+    let repository = db::connect(&format!(
+        "sqlite://{}",
+        dir.path().join("overlad.db").display()
+    ))
+    .await
+    .unwrap();
+    repository.migrate_up().await.unwrap();
+
+    repository
+}
+
+/// A database created for a single test on the `TEST_POSTGRES_URL` server.
+struct ScratchDatabase {
+    repository: Arc<dyn Repository>,
+    server_url: String,
+    name: String,
+}
+
+impl ScratchDatabase {
+    async fn create() -> Self {
+        let server_url = std::env::var("TEST_POSTGRES_URL")
+            .expect("TEST_POSTGRES_URL must point at a Postgres server");
+        let name = format!("overlad_test_{:016x}", rand::random::<u64>());
+
+        let mut connection = PgConnection::connect(&server_url).await.unwrap();
+        sqlx::query(&format!("CREATE DATABASE {name}"))
+            .execute(&mut connection)
+            .await
+            .unwrap();
+
+        let (server, _) = server_url.rsplit_once('/').unwrap();
+        let repository = db::connect(&format!("{server}/{name}")).await.unwrap();
+        repository.migrate_up().await.unwrap();
+
+        Self {
+            repository,
+            server_url,
+            name,
+        }
+    }
+
+    async fn drop(self) {
+        drop(self.repository);
+
+        let mut connection = PgConnection::connect(&self.server_url).await.unwrap();
+        sqlx::query(&format!("DROP DATABASE {} WITH (FORCE)", self.name))
+            .execute(&mut connection)
+            .await
+            .unwrap();
+    }
+}
+
+fn at(seconds: i64) -> NaiveDateTime {
+    DateTime::from_timestamp(1_700_000_000 + seconds, 0)
+        .unwrap()
+        .naive_utc()
+}
+
+fn metadata() -> ImageMetadata {
+    ImageMetadata {
+        width: 400,
+        height: 300,
+        size_bytes: 1234,
+    }
+}
+
+fn rendition(width: u32, height: u32) -> EncodedRendition {
+    EncodedRendition {
+        width,
+        height,
+        extension: "webp",
+        bytes: vec![0; 10],
+    }
+}
+
+async fn insert_user(repository: &dyn Repository, username: &str) -> i64 {
+    repository.insert_user(username, "hash").await.unwrap().id
+}
+
+/// Inserts an image uploaded at `at(seconds)`.
+async fn insert_image(repository: &dyn Repository, id: &str, user_id: i64, seconds: i64) {
+    repository
+        .insert_image(
+            id,
+            user_id,
+            "png",
+            &metadata(),
+            None,
+            &[rendition(400, 300)],
+        )
+        .await
+        .unwrap();
+    repository
+        .update_image_metadata(id, &metadata(), at(seconds))
+        .await
+        .unwrap();
+}
+
+/// Follows cursors through every page of `limit` images.
+async fn all_pages(
+    repository: &dyn Repository,
+    user_id: Option<i64>,
+    sort: ImageSort,
+    limit: u32,
+) -> Vec<String> {
+    let mut ids = Vec::new();
+    let mut cursor = None;
+
+    loop {
+        let page = repository
+            .get_image_page(user_id, sort, cursor.as_ref(), limit)
+            .await
+            .unwrap();
+        assert!(page.len() <= limit as usize);
+
+        let Some(last) = page.last() else {
+            return ids;
+        };
+        cursor = Some(ImageCursor {
+            created_at: last.created_at,
+            id: last.id.clone(),
+        });
+
+        ids.extend(page.into_iter().map(|db_image| db_image.id));
+    }
+}
+
+async fn migrations_are_all_applied(repository: &dyn Repository) {
+    let statuses = repository.migration_status().await.unwrap();
+
+    assert!(!statuses.is_empty());
+    assert!(statuses.iter().all(|status| status.applied));
+}
+
+async fn users(repository: &dyn Repository) {
+    let db_user = repository.insert_user("alice", "hash").await.unwrap();
+    assert_eq!(db_user.username, "alice");
+    assert_eq!(db_user.passhash, "hash");
+
+    assert!(repository.insert_user("alice", "other").await.is_err());
+
+    let by_id = repository
+        .get_user_by_id(db_user.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(by_id.username, "alice");
+
+    let by_username = repository
+        .get_user_by_username("alice")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(by_username.id, db_user.id);
+
+    assert!(
+        repository
+            .get_user_by_id(db_user.id + 1)
+            .await
+            .unwrap()
+            .is_none()
+    );
+    assert!(
+        repository
+            .get_user_by_username("bob")
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    repository
+        .update_password(db_user.id, "new hash", Utc::now().naive_utc())
+        .await
+        .unwrap();
+    let updated = repository
+        .get_user_by_id(db_user.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(updated.passhash, "new hash");
+}
+
+async fn image_pages(repository: &dyn Repository) {
+    let alice = insert_user(repository, "alice").await;
+    let bob = insert_user(repository, "bob").await;
+
+    // `b` and `c` share an upload time, so the id breaks the tie.
+    insert_image(repository, "a", alice, 0).await;
+    insert_image(repository, "c", bob, 1).await;
+    insert_image(repository, "b", alice, 1).await;
+    insert_image(repository, "d", alice, 2).await;
+    insert_image(repository, "e", bob, 3).await;
+
+    for limit in [1, 2, 5, 10] {
+        assert_eq!(
+            all_pages(repository, None, ImageSort::Newest, limit).await,
+            ["e", "d", "c", "b", "a"]
+        );
+        assert_eq!(
+            all_pages(repository, None, ImageSort::Oldest, limit).await,
+            ["a", "b", "c", "d", "e"]
+        );
+        assert_eq!(
+            all_pages(repository, Some(alice), ImageSort::Newest, limit).await,
+            ["d", "b", "a"]
+        );
+        assert_eq!(
+            all_pages(repository, Some(bob), ImageSort::Oldest, limit).await,
+            ["c", "e"]
+        );
+    }
+
+    let page = repository
+        .get_image_page(None, ImageSort::Newest, None, 1)
+        .await
+        .unwrap();
+    assert_eq!(page[0].username, "bob");
+    assert_eq!(page[0].created_at, at(3));
+}
+
+async fn renditions(repository: &dyn Repository) {
+    let alice = insert_user(repository, "alice").await;
+
+    let db_image = repository
+        .insert_image(
+            "a",
+            alice,
+            "png",
+            &metadata(),
+            Some("cat.png"),
+            &[rendition(400, 300), rendition(200, 150)],
+        )
+        .await
+        .unwrap();
+    assert_eq!(db_image.original_filename.as_deref(), Some("cat.png"));
+    assert_eq!((db_image.width, db_image.height), (400, 300));
+
+    repository
+        .insert_renditions("a", &[rendition(100, 75)])
+        .await
+        .unwrap();
+
+    let db_renditions = repository.get_renditions("a").await.unwrap();
+    assert_eq!(
+        db_renditions
+            .iter()
+            .map(|db_rendition| (db_rendition.width, db_rendition.height))
+            .collect::<Vec<_>>(),
+        [(100, 75), (200, 150), (400, 300)]
+    );
+    assert_eq!(db_renditions[0].key(), "a-100.webp");
+
+    // Renditions are all or nothing, so a duplicate rolls back the batch.
+    assert!(
+        repository
+            .insert_renditions("a", &[rendition(50, 37), rendition(100, 75)])
+            .await
+            .is_err()
+    );
+    assert_eq!(repository.get_renditions("a").await.unwrap().len(), 3);
+}
+
+async fn deleting_an_image_deletes_its_renditions(repository: &dyn Repository) {
+    let alice = insert_user(repository, "alice").await;
+    insert_image(repository, "a", alice, 0).await;
+
+    repository.delete_image("a").await.unwrap();
+
+    assert!(repository.get_image_by_id("a").await.unwrap().is_none());
+    assert!(repository.get_renditions("a").await.unwrap().is_empty());
+}
+
+async fn backfill_queries(repository: &dyn Repository) {
+    let alice = insert_user(repository, "alice").await;
+    insert_image(repository, "a", alice, 0).await;
+    repository
+        .insert_image(
+            "b",
+            alice,
+            "png",
+            &ImageMetadata {
+                width: 0,
+                height: 0,
+                size_bytes: 0,
+            },
+            None,
+            &[],
+        )
+        .await
+        .unwrap();
+
+    let ids = |db_images: Vec<db::image::DbImage>| {
+        db_images
+            .into_iter()
+            .map(|db_image| db_image.id)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        ids(repository.get_images_missing_metadata().await.unwrap()),
+        ["b"]
+    );
+    assert_eq!(
+        ids(repository.get_images_missing_renditions().await.unwrap()),
+        ["b"]
+    );
+}
+
+async fn refresh_tokens(repository: &dyn Repository) {
+    let alice = insert_user(repository, "alice").await;
+
+    repository
+        .insert_refresh_token("valid", alice, at(0), at(100))
+        .await
+        .unwrap();
+    repository
+        .insert_refresh_token("expired", alice, at(0), at(10))
+        .await
+        .unwrap();
+    repository
+        .insert_refresh_token("revoked", alice, at(0), at(100))
+        .await
+        .unwrap();
+
+    repository
+        .revoke_refresh_token("revoked", at(20))
+        .await
+        .unwrap();
+
+    // Consuming revokes, so each token is exchanged at most once.
+    assert_eq!(
+        repository
+            .consume_refresh_token("valid", at(20))
+            .await
+            .unwrap(),
+        Some(alice)
+    );
+    assert_eq!(
+        repository
+            .consume_refresh_token("valid", at(21))
+            .await
+            .unwrap(),
+        None
+    );
+
+    for token_hash in ["expired", "revoked", "unknown"] {
+        assert_eq!(
+            repository
+                .consume_refresh_token(token_hash, at(20))
+                .await
+                .unwrap(),
+            None,
+            "{token_hash} was consumed"
+        );
+    }
+}
+
+async fn revoking_every_refresh_token_of_a_user(repository: &dyn Repository) {
+    let alice = insert_user(repository, "alice").await;
+    let bob = insert_user(repository, "bob").await;
+
+    for (token_hash, user_id) in [("alice 1", alice), ("alice 2", alice), ("bob", bob)] {
+        repository
+            .insert_refresh_token(token_hash, user_id, at(0), at(100))
+            .await
+            .unwrap();
+    }
+
+    repository
+        .revoke_user_refresh_tokens(alice, at(10))
+        .await
+        .unwrap();
+
+    for token_hash in ["alice 1", "alice 2"] {
+        assert_eq!(
+            repository
+                .consume_refresh_token(token_hash, at(20))
+                .await
+                .unwrap(),
+            None
+        );
+    }
+    assert_eq!(
+        repository
+            .consume_refresh_token("bob", at(20))
+            .await
+            .unwrap(),
+        Some(bob)
+    );
+}
+
+async fn updating_a_password_revokes_refresh_tokens(repository: &dyn Repository) {
+    let alice = insert_user(repository, "alice").await;
+    repository
+        .insert_refresh_token("alice", alice, at(0), at(100))
+        .await
+        .unwrap();
+
+    repository
+        .update_password(alice, "new hash", at(10))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        repository
+            .consume_refresh_token("alice", at(20))
+            .await
+            .unwrap(),
+        None
+    );
+}
@@ -0,0 +1,153 @@
+//! S3 storage against a minimal path-style S3 service, which keeps objects in
+//! memory and checks that each request is signed.
+
+use std::{
+    collections::HashMap,
+    io,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    Router,
+    body::Bytes,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use chrono::{TimeZone, Utc};
+use overlad_backend::storage::{
+    Storage,
+    s3::{S3Config, S3Storage},
+};
+use tokio::net::TcpListener;
+
+const BUCKET: &str = "images";
+const LAST_MODIFIED: &str = "Sun, 18 Oct 2026 12:00:00 GMT";
+
+type Objects = Arc<Mutex<HashMap<String, Bytes>>>;
+
+fn signed(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("AWS4-HMAC-SHA256 Credential=access/"))
+}
+
+fn object_headers(bytes: &Bytes) -> [(header::HeaderName, String); 3] {
+    [
+        (header::CONTENT_LENGTH, bytes.len().to_string()),
+        (header::ETAG, format!("\"{}\"", bytes.len())),
+        (header::LAST_MODIFIED, String::from(LAST_MODIFIED)),
+    ]
+}
+
+async fn get_object(
+    State(objects): State<Objects>,
+    Path((bucket, key)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    if bucket != BUCKET || !signed(&headers) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    match objects.lock().unwrap().get(&key) {
+        Some(bytes) => (object_headers(bytes), bytes.clone()).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn put_object(
+    State(objects): State<Objects>,
+    Path((bucket, key)): Path<(String, String)>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if bucket != BUCKET || !signed(&headers) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let etag = format!("\"{}\"", body.len());
+    objects.lock().unwrap().insert(key, body);
+
+    [(header::ETAG, etag)].into_response()
+}
+
+/// Answers not found for missing objects, which S3 itself does not, to check
+/// that deleting one still succeeds.
+async fn delete_object(
+    State(objects): State<Objects>,
+    Path((bucket, key)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> StatusCode {
+    if bucket != BUCKET || !signed(&headers) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    match objects.lock().unwrap().remove(&key) {
+        Some(_) => StatusCode::NO_CONTENT,
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
+async fn s3_storage() -> S3Storage {
+    let objects = Objects::default();
+    let router = Router::new()
+        .route(
+            "/{bucket}/{*key}",
+            get(get_object).put(put_object).delete(delete_object),
+        )
+        .with_state(objects);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+    S3Storage::new(S3Config {
+        endpoint: format!("http://{address}"),
+        bucket: String::from(BUCKET),
+        region: String::from("us-east-1"),
+        access_key_id: String::from("access"),
+        secret_access_key: String::from("secret"),
+    })
+    .unwrap()
+}
+
+#[tokio::test]
+async fn objects_are_stored_read_and_deleted() {
+    let storage = s3_storage().await;
+    let bytes = b"not really an image".to_vec();
+
+    storage.put("abc-256.webp", bytes.clone()).await.unwrap();
+
+    assert_eq!(storage.get("abc-256.webp").await.unwrap(), bytes);
+    assert!(storage.exists("abc-256.webp").await.unwrap());
+    assert_eq!(
+        storage.last_modified("abc-256.webp").await.unwrap(),
+        Utc.with_ymd_and_hms(2026, 10, 18, 12, 0, 0).unwrap()
+    );
+
+    storage.delete("abc-256.webp").await.unwrap();
+
+    assert!(!storage.exists("abc-256.webp").await.unwrap());
+}
+
+#[tokio::test]
+async fn missing_objects_are_not_found() {
+    let storage = s3_storage().await;
+
+    assert_eq!(
+        storage.get("missing.webp").await.unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+    assert_eq!(
+        storage
+            .last_modified("missing.webp")
+            .await
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::NotFound
+    );
+    assert!(!storage.exists("missing.webp").await.unwrap());
+    storage.delete("missing.webp").await.unwrap();
+}
@@ -0,0 +1,257 @@
+//! Uploads are turned upright and nothing served from them carries the
+//! uploader's metadata.
+
+use std::io::Cursor;
+
+use axum::http::StatusCode;
+use image::{ImageFormat, RgbImage, Rgba, RgbaImage, codecs::jpeg::JpegEncoder};
+use overlad_api::Image;
+use overlad_lib::test_support::{
+    GIF_METADATA_MARKERS, contains, webp_with_metadata, with_gif_metadata,
+};
+
+use common::{TestApp, gif};
+
+mod common;
+
+const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+const GREEN: Rgba<u8> = Rgba([0, 255, 0, 255]);
+const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+/// Metadata markers of EXIF, XMP and color profiles in JPEG, PNG and WebP.
+const METADATA_MARKERS: [&[u8]; 7] = [
+    b"Exif",
+    b"eXIf",
+    b"EXIF",
+    b"http://ns.adobe.com/xap/1.0/",
+    b"ICC_PROFILE",
+    b"iCCP",
+    b"ICCP",
+];
+
+/// A 64×32 image with red, green, blue and white quadrants, clockwise from
+/// the top left.
+fn quadrants() -> RgbaImage {
+    RgbaImage::from_fn(64, 32, |x, y| match (x < 32, y < 16) {
+        (true, true) => RED,
+        (false, true) => GREEN,
+        (false, false) => WHITE,
+        (true, false) => BLUE,
+    })
+}
+
+/// A JPEG segment with `marker`, such as APP1 for EXIF and XMP.
+fn segment(marker: u8, data: &[u8]) -> Vec<u8> {
+    let mut segment = vec![0xFF, marker];
+    segment.extend_from_slice(&(data.len() as u16 + 2).to_be_bytes());
+    segment.extend_from_slice(data);
+
+    segment
+}
+
+/// EXIF data with only an orientation tag.
+fn exif(orientation: u16) -> Vec<u8> {
+    let mut exif = b"MM\x00\x2A\x00\x00\x00\x08\x00\x01\x01\x12\x00\x03\x00\x00\x00\x01".to_vec();
+    exif.extend_from_slice(&orientation.to_be_bytes());
+    exif.extend_from_slice(&[0; 6]);
+
+    exif
+}
+
+/// [`quadrants`] as a JPEG with EXIF `orientation`, XMP and a color profile.
+fn jpeg(orientation: u16) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    RgbImage::from_fn(64, 32, |x, y| {
+        let Rgba([r, g, b, _]) = *quadrants().get_pixel(x, y);
+        image::Rgb([r, g, b])
+    })
+    .write_with_encoder(JpegEncoder::new_with_quality(&mut encoded, 95))
+    .unwrap();
+
+    let mut bytes = encoded[..2].to_vec();
+    bytes.extend(segment(
+        0xE1,
+        &[&b"Exif\0\0"[..], &exif(orientation)].concat(),
+    ));
+    bytes.extend(segment(
+        0xE1,
+        b"http://ns.adobe.com/xap/1.0/\0<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"/>",
+    ));
+    bytes.extend(segment(0xE2, b"ICC_PROFILE\0\x01\x01icc!"));
+    bytes.extend_from_slice(&encoded[2..]);
+
+    bytes
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, byte| {
+        (0..8).fold(crc ^ u32::from(*byte), |crc, _| {
+            (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+    chunk.extend_from_slice(kind);
+    chunk.extend_from_slice(data);
+    chunk.extend_from_slice(&crc32(&chunk[4..]).to_be_bytes());
+
+    chunk
+}
+
+/// [`quadrants`] as a PNG with EXIF, XMP and a color profile after its header.
+fn png() -> Vec<u8> {
+    let mut encoded = Vec::new();
+    quadrants()
+        .write_to(&mut Cursor::new(&mut encoded), ImageFormat::Png)
+        .unwrap();
+
+    // The signature and IHDR chunk.
+    let header_len = 8 + 25;
+    let mut bytes = encoded[..header_len].to_vec();
+    bytes.extend(png_chunk(
+        b"iCCP",
+        b"icc\0\0\x78\x9C\x03\x00\x00\x00\x00\x01",
+    ));
+    bytes.extend(png_chunk(b"eXIf", &exif(1)));
+    bytes.extend(png_chunk(
+        b"iTXt",
+        b"XML:com.adobe.xmp\0\0\0\0\0http://ns.adobe.com/xap/1.0/",
+    ));
+    bytes.extend_from_slice(&encoded[header_len..]);
+
+    bytes
+}
+
+fn assert_close(actual: Rgba<u8>, expected: Rgba<u8>, context: &str) {
+    let close = actual
+        .0
+        .iter()
+        .zip(expected.0)
+        .all(|(actual, expected)| actual.abs_diff(expected) <= 48);
+
+    assert!(close, "{context}: {actual:?} is not close to {expected:?}");
+}
+
+/// Uploads `bytes` and returns the image with every file served from it:
+/// its renditions and a rendered overlay.
+async fn upload(app: &TestApp, bytes: &[u8]) -> (Image, Vec<Vec<u8>>) {
+    let tokens = app.register("alice").await;
+    let image = app.upload(&tokens.token, bytes, &[]).await.json::<Image>();
+
+    let mut served = Vec::new();
+
+    for rendition in app.state.db.get_renditions(&image.id).await.unwrap() {
+        served.push(app.state.storage.get(&rendition.key()).await.unwrap());
+    }
+
+    let response = app
+        .get(&format!("/overlay/{}?text=hi", image.id), None)
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    served.push(response.body.to_vec());
+
+    (image, served)
+}
+
+#[tokio::test]
+async fn uploads_are_turned_upright() {
+    // The upright quadrants clockwise from the top left for each EXIF
+    // orientation, and whether the sides are swapped.
+    let cases = [
+        (1, [RED, GREEN, WHITE, BLUE], false),
+        (2, [GREEN, RED, BLUE, WHITE], false),
+        (3, [WHITE, BLUE, RED, GREEN], false),
+        (4, [BLUE, WHITE, GREEN, RED], false),
+        (5, [RED, BLUE, WHITE, GREEN], true),
+        (6, [BLUE, RED, GREEN, WHITE], true),
+        (7, [WHITE, GREEN, RED, BLUE], true),
+        (8, [GREEN, WHITE, BLUE, RED], true),
+    ];
+
+    for (orientation, expected, swapped) in cases {
+        let app = TestApp::new().await;
+        let tokens = app.register("alice").await;
+        let image = app
+            .upload(&tokens.token, &jpeg(orientation), &[])
+            .await
+            .json::<Image>();
+
+        let (width, height) = if swapped { (32, 64) } else { (64, 32) };
+        assert_eq!(
+            (image.width, image.height),
+            (width, height),
+            "{orientation}"
+        );
+
+        let response = app.get(&format!("/overlay/{}.png", image.id), None).await;
+        let rendered = image::load_from_memory(&response.body)
+            .unwrap()
+            .into_rgba8();
+        assert_eq!(rendered.dimensions(), (width, height), "{orientation}");
+
+        let (x1, x2, y1, y2) = (width / 4, width * 3 / 4, height / 4, height * 3 / 4);
+        for ((x, y), expected) in [(x1, y1), (x2, y1), (x2, y2), (x1, y2)]
+            .into_iter()
+            .zip(expected)
+        {
+            assert_close(
+                *rendered.get_pixel(x, y),
+                expected,
+                &format!("orientation {orientation} at {x},{y}"),
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn still_metadata_is_stripped() {
+    for (name, bytes) in [
+        ("jpeg", jpeg(1)),
+        ("png", png()),
+        ("webp", webp_with_metadata(&quadrants())),
+    ] {
+        let app = TestApp::new().await;
+
+        assert!(
+            METADATA_MARKERS
+                .iter()
+                .any(|marker| contains(&bytes, marker)),
+            "{name}"
+        );
+
+        let (image, served) = upload(&app, &bytes).await;
+        assert_eq!((image.width, image.height), (64, 32), "{name}");
+
+        for served in served {
+            for marker in METADATA_MARKERS {
+                assert!(
+                    !contains(&served, marker),
+                    "{name} serves {}",
+                    String::from_utf8_lossy(marker)
+                );
+            }
+
+            image::load_from_memory(&served).unwrap();
+        }
+    }
+}
+
+#[tokio::test]
+async fn animation_metadata_is_stripped() {
+    let app = TestApp::new().await;
+    let bytes = with_gif_metadata(gif(8, 8, 2));
+
+    let (_, served) = upload(&app, &bytes).await;
+
+    for served in served {
+        for marker in GIF_METADATA_MARKERS {
+            assert!(contains(&bytes, marker));
+            assert!(!contains(&served, marker));
+        }
+
+        image::load_from_memory(&served).unwrap();
+    }
+}
@@ -1,9 +1,11 @@
 use std::io::Cursor;
 
-use ab_glyph::FontRef;
+use ab_glyph::FontArc;
 use base64::{Engine, prelude::BASE64_STANDARD};
-use image::{ImageFormat, Rgba, RgbaImage};
-use overlad_lib::overlay;
+use gloo::net::http::Request;
+use image::{ImageFormat, RgbaImage};
+use overlad_api::DEFAULT_FONT;
+use overlad_lib::{TextLayer, overlay};
 use yew::prelude::*;
 
 #[derive(Properties, PartialEq)]
@@ -11,19 +13,10 @@ pub struct ClientOverlayProps {
     pub image: RgbaImage,
 
     #[prop_or_default]
-    pub text: String,
+    pub layers: Vec<TextLayer>,
 
-    #[prop_or(Rgba([255, 255, 255, 255]))]
-    pub text_color: Rgba<u8>,
-
-    #[prop_or(1.0)]
-    pub text_scale: f64,
-
-    #[prop_or(Rgba([0, 0, 0, 255]))]
-    pub outline_color: Rgba<u8>,
-
-    #[prop_or(0.0)]
-    pub outline_thickness: f64,
+    #[prop_or(AttrValue::Static(DEFAULT_FONT))]
+    pub font: AttrValue,
 
     #[prop_or_default]
     pub classes: Classes,
@@ -33,36 +26,51 @@ pub struct ClientOverlayProps {
 pub fn ClientOverlay(
     ClientOverlayProps {
         image,
-        text,
-        text_color,
-        text_scale,
-        outline_color,
-        outline_thickness,
+        layers,
+        font,
         classes,
     }: &ClientOverlayProps,
 ) -> Html {
-    let font = FontRef::try_from_slice(include_bytes!("../../../roboto.ttf")).unwrap();
+    let loaded_font_state = use_state(Option::<(AttrValue, FontArc)>::default);
 
-    let overlaid_image_memo = use_memo(
-        (
-            image.clone(),
-            text.clone(),
-            *text_color,
-            *text_scale,
-            *outline_color,
-            *outline_thickness,
+    use_effect_with(font.clone(), {
+        let loaded_font_state = loaded_font_state.clone();
+
+        move |font| {
+            let font = font.clone();
+            let loaded_font_state = loaded_font_state.clone();
+
+            wasm_bindgen_futures::spawn_local(async move {
+                let font_response = Request::get(&format!("/api/fonts/{font}"))
+                    .send()
+                    .await
+                    .unwrap();
+
+                if font_response.ok() {
+                    let font_bytes = font_response.binary().await.unwrap();
+
+                    if let Ok(font_arc) = FontArc::try_from_vec(font_bytes) {
+                        loaded_font_state.set(Some((font, font_arc)));
+                    }
+                }
+            });
+        }
+    });
+
+    // Render with the bundled font until the requested one has been fetched.
+    let (loaded_font_name, loaded_font) = match &*loaded_font_state {
+        Some((loaded_font_name, loaded_font)) if loaded_font_name == font => {
+            (Some(loaded_font_name.clone()), loaded_font.clone())
+        }
+        _ => (
+            None,
+            FontArc::try_from_slice(include_bytes!("../../../roboto.ttf")).unwrap(),
         ),
-        |(image, text, text_color, text_scale, outline_color, outline_thickness)| {
-            overlay(
-                image.clone(),
-                text.clone(),
-                *text_color,
-                *outline_color,
-                *text_scale,
-                *outline_thickness,
-                font,
-            )
-        },
+    };
+
+    let overlaid_image_memo = use_memo(
+        (image.clone(), layers.clone(), loaded_font_name),
+        |(image, layers, _)| overlay(image.clone(), layers, loaded_font),
     );
 
     let overlaid_image_base64_memo = use_memo(overlaid_image_memo, |overlaid_image_memo| {
@@ -0,0 +1,374 @@
+use std::{cell::Cell, rc::Rc};
+
+use gloo::net::http::Request;
+use overlad_api::{Image, ImageSort, ImagesResponse};
+use web_sys::{
+    Element, HtmlSelectElement, IntersectionObserver, IntersectionObserverEntry,
+    js_sys::Array,
+    wasm_bindgen::{JsCast, closure::Closure},
+};
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::{
+    Route,
+    components::{
+        button::{Button, ButtonType},
+        token_provider::{TokenContext, send_with_token},
+    },
+    hooks::use_rendition_widths,
+    util::{WithToken, error_message, image_summary},
+};
+
+const PAGE_SIZE: u32 = 24;
+
+/// Height in CSS pixels of thumbnails from the `sm` breakpoint up.
+const THUMBNAIL_HEIGHT: u32 = 256;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Gallery {
+    sort: ImageSort,
+    images: Vec<Image>,
+    next_cursor: Option<String>,
+    loading: bool,
+    done: bool,
+
+    /// Whether the last page failed to load, which pauses loading until the
+    /// user retries.
+    failed: bool,
+}
+
+enum GalleryAction {
+    Sort(ImageSort),
+    Loading,
+    Loaded(ImageSort, ImagesResponse),
+    Failed,
+    Retry,
+    Removed(String),
+}
+
+impl Reducible for Gallery {
+    type Action = GalleryAction;
+
+    fn reduce(self: Rc<Self>, action: Self::Action) -> Rc<Self> {
+        match action {
+            GalleryAction::Sort(sort) => Gallery {
+                sort,
+                ..Gallery::default()
+            },
+            GalleryAction::Loading => Gallery {
+                loading: true,
+                ..(*self).clone()
+            },
+            // A page requested before the sort changed belongs to the old list.
+            GalleryAction::Loaded(sort, _) if sort != self.sort => return self,
+            GalleryAction::Loaded(sort, images_response) => {
+                let mut images = self.images.clone();
+                images.extend(images_response.images);
+
+                Gallery {
+                    sort,
+                    images,
+                    done: images_response.next_cursor.is_none(),
+                    next_cursor: images_response.next_cursor,
+                    loading: false,
+                    failed: false,
+                }
+            }
+            GalleryAction::Failed => Gallery {
+                loading: false,
+                failed: true,
+                ..(*self).clone()
+            },
+            GalleryAction::Retry => Gallery {
+                failed: false,
+                ..(*self).clone()
+            },
+            GalleryAction::Removed(id) => Gallery {
+                images: self
+                    .images
+                    .iter()
+                    .filter(|image| image.id != id)
+                    .cloned()
+                    .collect(),
+                ..(*self).clone()
+            },
+        }
+        .into()
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ImageGalleryProps {
+    /// The paginated list endpoint to load images from.
+    pub url: AttrValue,
+
+    /// Shows a delete button on each image, for galleries of the signed in
+    /// user's own images.
+    #[prop_or_default]
+    pub deletable: bool,
+}
+
+/// A grid of images that loads the next page as its end scrolls into view.
+#[function_component]
+pub fn ImageGallery(ImageGalleryProps { url, deletable }: &ImageGalleryProps) -> Html {
+    let token_context = use_context::<TokenContext>().expect("no token context found");
+
+    // Thumbnails are requested at rendition widths so the backend can skip
+    // decoding the full size image.
+    let maybe_rendition_widths = use_rendition_widths();
+    let gallery = use_reducer(Gallery::default);
+    let sentinel_ref = use_node_ref();
+    let confirming_delete_state = use_state(Option::<String>::default);
+    let error_text_state = use_state(Option::<String>::default);
+
+    // The observer is recreated for every page so its callback always requests
+    // the page after the current cursor. Observing fires once right away, which
+    // keeps loading pages until the grid fills the screen.
+    use_effect_with(
+        (
+            url.clone(),
+            gallery.sort,
+            gallery.next_cursor.clone(),
+            gallery.loading || gallery.done || gallery.failed,
+        ),
+        {
+            let gallery = gallery.clone();
+            let sentinel_ref = sentinel_ref.clone();
+
+            move |(url, sort, next_cursor, paused)| {
+                let maybe_observer = (!*paused).then(|| {
+                    let url = url.clone();
+                    let sort = *sort;
+                    let next_cursor = next_cursor.clone();
+                    let requested = Rc::new(Cell::new(false));
+
+                    let on_intersect = Closure::<dyn FnMut(Array)>::new(move |entries: Array| {
+                        let is_intersecting = entries.iter().any(|entry| {
+                            entry
+                                .unchecked_into::<IntersectionObserverEntry>()
+                                .is_intersecting()
+                        });
+
+                        if !is_intersecting || requested.replace(true) {
+                            return;
+                        }
+
+                        gallery.dispatch(GalleryAction::Loading);
+
+                        let gallery = gallery.clone();
+                        let mut page_url = format!(
+                            "{url}?limit={PAGE_SIZE}&sort={}",
+                            serde_plain::to_string(&sort).unwrap()
+                        );
+
+                        if let Some(cursor) = &next_cursor {
+                            page_url.push_str(&format!("&cursor={cursor}"));
+                        }
+
+                        wasm_bindgen_futures::spawn_local(async move {
+                            let maybe_images_response = match Request::get(&page_url).send().await
+                            {
+                                Ok(response) if response.ok() => {
+                                    response.json::<ImagesResponse>().await.ok()
+                                }
+                                _ => None,
+                            };
+
+                            gallery.dispatch(match maybe_images_response {
+                                Some(images_response) => {
+                                    GalleryAction::Loaded(sort, images_response)
+                                }
+                                None => GalleryAction::Failed,
+                            });
+                        });
+                    });
+
+                    let observer =
+                        IntersectionObserver::new(on_intersect.as_ref().unchecked_ref()).unwrap();
+
+                    if let Some(sentinel) = sentinel_ref.cast::<Element>() {
+                        observer.observe(&sentinel);
+                    }
+
+                    (observer, on_intersect)
+                });
+
+                move || {
+                    if let Some((observer, _on_intersect)) = maybe_observer {
+                        observer.disconnect();
+                    }
+                }
+            }
+        },
+    );
+
+    let on_sort_change = {
+        let gallery = gallery.clone();
+
+        Callback::from(move |event: Event| {
+            if let Some(select) = event
+                .target()
+                .and_then(|target| target.dyn_into::<HtmlSelectElement>().ok())
+                && let Ok(sort) = serde_plain::from_str::<ImageSort>(&select.value())
+            {
+                gallery.dispatch(GalleryAction::Sort(sort));
+            }
+        })
+    };
+
+    let on_retry_click = {
+        let gallery = gallery.clone();
+
+        Callback::from(move |_| gallery.dispatch(GalleryAction::Retry))
+    };
+
+    let on_delete = {
+        let gallery = gallery.clone();
+        let confirming_delete_state = confirming_delete_state.clone();
+        let error_text_state = error_text_state.clone();
+
+        Callback::from(move |id: String| {
+            let token_context = token_context.clone();
+            let gallery = gallery.clone();
+            let confirming_delete_state = confirming_delete_state.clone();
+            let error_text_state = error_text_state.clone();
+
+            if let Some(token) = token_context.0.clone() {
+                wasm_bindgen_futures::spawn_local(async move {
+                    let delete_response = send_with_token(&token_context, token, |token| {
+                        Request::delete(&format!("/api/image/{id}"))
+                            .with_token(token)
+                            .build()
+                    })
+                    .await
+                    .unwrap();
+
+                    confirming_delete_state.set(None);
+
+                    if delete_response.ok() {
+                        error_text_state.set(None);
+                        gallery.dispatch(GalleryAction::Removed(id));
+                    } else {
+                        let delete_response_text = delete_response.text().await.unwrap();
+
+                        error_text_state.set(Some(error_message(delete_response_text)));
+                    }
+                });
+            }
+        })
+    };
+
+    html! {
+        <div class="flex flex-col gap-2">
+            <div class="flex items-center">
+                <label class="pr-2 grow-0">{ "Sort" }</label>
+                <select onchange={on_sort_change} class="outline-offset-1 focus:outline-1 border p-1 rounded-sm">
+                    <option value="newest" selected={gallery.sort == ImageSort::Newest}>{ "Newest" }</option>
+                    <option value="oldest" selected={gallery.sort == ImageSort::Oldest}>{ "Oldest" }</option>
+                </select>
+            </div>
+            if let Some(error_text) = &*error_text_state {
+                <p class="text-red-500">{error_text}</p>
+            }
+            <section class="flex flex-col flex-wrap sm:flex-row gap-2">
+                {
+                    gallery.images.iter().map(|image| {
+                        let id = image.id.clone();
+                        let is_confirming = confirming_delete_state.as_deref() == Some(id.as_str());
+
+                        let on_delete_click = {
+                            let confirming_delete_state = confirming_delete_state.clone();
+                            let id = id.clone();
+
+                            Callback::from(move |_| confirming_delete_state.set(Some(id.clone())))
+                        };
+
+                        let on_confirm_click = {
+                            let on_delete = on_delete.clone();
+                            let id = id.clone();
+
+                            Callback::from(move |_| on_delete.emit(id.clone()))
+                        };
+
+                        let on_cancel_click = {
+                            let confirming_delete_state = confirming_delete_state.clone();
+
+                            Callback::from(move |_| confirming_delete_state.set(None))
+                        };
+
+                        html! {
+                            <div class="border flex flex-col">
+                                <Link<Route> to={Route::Image { id }} classes="flex flex-col">
+                                    if let Some(rendition_widths) = &maybe_rendition_widths {
+                                        <img
+                                            src={thumbnail_url(image, rendition_widths.first().copied().unwrap_or(image.width))}
+                                            srcset={thumbnail_srcset(image, rendition_widths)}
+                                            sizes={thumbnail_sizes(image)}
+                                            class="sm:h-64"
+                                        />
+                                    } else {
+                                        <div class="sm:h-64" />
+                                    }
+                                    <p class="px-1 text-sm text-gray-500">{ image_summary(image) }</p>
+                                </Link<Route>>
+                                if *deletable {
+                                    <div class="flex items-center gap-2 p-1">
+                                        if is_confirming {
+                                            <span class="grow text-sm">{ "Delete this image?" }</span>
+                                            <Button r#type={ButtonType::Button} onclick={on_confirm_click}>{ "Confirm" }</Button>
+                                            <Button r#type={ButtonType::Button} onclick={on_cancel_click}>{ "Cancel" }</Button>
+                                        } else {
+                                            <Button r#type={ButtonType::Button} onclick={on_delete_click} classes="grow">{ "Delete" }</Button>
+                                        }
+                                    </div>
+                                }
+                            </div>
+                        }
+                    }).collect::<Html>()
+                }
+            </section>
+            if gallery.loading {
+                <p class="text-gray-500">{ "Loading…" }</p>
+            }
+            if gallery.failed {
+                <div class="flex items-center gap-2">
+                    <p class="text-red-500">{ "Failed to load images" }</p>
+                    <Button r#type={ButtonType::Button} onclick={on_retry_click}>{ "Retry" }</Button>
+                </div>
+            }
+            <div ref={sentinel_ref} />
+        </div>
+    }
+}
+
+/// The overlay of an image resized to `width`, or at full size when it is no
+/// wider than that.
+fn thumbnail_url(image: &Image, width: u32) -> String {
+    if width < image.width {
+        format!("/api/overlay/{}?resize_width={width}", image.id)
+    } else {
+        format!("/api/overlay/{}", image.id)
+    }
+}
+
+/// A `srcset` of the rendition widths narrower than the image, and the image
+/// at full size.
+fn thumbnail_srcset(image: &Image, rendition_widths: &[u32]) -> String {
+    rendition_widths
+        .iter()
+        .filter(|width| **width < image.width)
+        .chain([&image.width])
+        .map(|width| format!("{} {width}w", thumbnail_url(image, *width)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The displayed width of a thumbnail, which is full width on small screens
+/// and a fixed height otherwise.
+fn thumbnail_sizes(image: &Image) -> String {
+    let width =
+        u64::from(THUMBNAIL_HEIGHT) * u64::from(image.width) / u64::from(image.height.max(1));
+
+    format!("(min-width: 640px) {width}px, 100vw")
+}
@@ -1,4 +1,5 @@
 pub mod button;
 pub mod client_overlay;
+pub mod image_gallery;
 pub mod nav;
 pub mod token_provider;
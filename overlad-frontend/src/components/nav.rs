@@ -1,22 +1,15 @@
-use jwt::{Header, Token, Unverified};
-use overlad_api::TokenClaims;
 use yew::prelude::*;
 use yew_nav::{NavLink, NavMenuButton, NavMenuStateContext};
 use yew_router::{Routable, components::Link};
 
-use crate::{Route, components::token_provider::TokenContext};
+use crate::{Route, hooks::use_me};
 
 #[function_component]
 pub fn NavBar() -> Html {
-    let token_reducer = use_context::<TokenContext>().expect("no token context found");
     let nav_menu_state_reducer =
         use_context::<NavMenuStateContext>().expect("no nav menu state context found");
 
-    let maybe_user_id = token_reducer.0.as_ref().map(|token_string |{
-        let token = Token::<Header, TokenClaims, Unverified<'_>>::parse_unverified(token_string).unwrap();
-        let claims: &TokenClaims = token.claims();
-        claims.sub
-    });
+    let maybe_user_id = use_me().map(|user| user.id);
 
     html! {
         <nav class="flex justify-between items-center relative px-4 py-2 bg-inherit">
@@ -1,12 +1,36 @@
-use gloo::storage::{LocalStorage, Storage, errors::StorageError};
+use gloo::{
+    events::EventListener,
+    net::http::{Request, Response},
+    storage::{LocalStorage, Storage, errors::StorageError},
+    timers::callback::Timeout,
+    utils::window,
+};
+use overlad_api::{RefreshTokenRequest, TokenResponse};
+use web_sys::{StorageEvent, js_sys::Date, wasm_bindgen::JsCast};
 use yew::prelude::*;
 
+use crate::util::token_claims;
+
+const TOKEN_KEY: &str = "token";
+const REFRESH_TOKEN_KEY: &str = "refresh_token";
+
+/// How long before the access token expires it is refreshed, in milliseconds.
+const REFRESH_MARGIN_MS: f64 = 30_000.0;
+
+/// The longest delay browsers wait for a timeout, in milliseconds. Longer ones
+/// fire right away, so tokens living longer are refreshed early instead.
+const MAX_TIMEOUT_MS: f64 = i32::MAX as f64;
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Token(pub Option<String>);
 
 pub enum TokenAction {
-    Set(String),
+    Set(TokenResponse),
     Clear,
+
+    /// Adopts the tokens in storage, which other tabs share and may have
+    /// rotated or cleared.
+    Reload,
 }
 
 impl Reducible for Token {
@@ -14,8 +38,19 @@ impl Reducible for Token {
 
     fn reduce(self: std::rc::Rc<Self>, action: Self::Action) -> std::rc::Rc<Self> {
         match action {
-            TokenAction::Set(token) => Token(Some(token)),
-            TokenAction::Clear => Token(None),
+            TokenAction::Set(token_response) => {
+                LocalStorage::set(TOKEN_KEY, &token_response.token).unwrap();
+                LocalStorage::set(REFRESH_TOKEN_KEY, &token_response.refresh_token).unwrap();
+
+                Token(Some(token_response.token))
+            }
+            TokenAction::Clear => {
+                LocalStorage::delete(TOKEN_KEY);
+                LocalStorage::delete(REFRESH_TOKEN_KEY);
+
+                Token(None)
+            }
+            TokenAction::Reload => Token(LocalStorage::get(TOKEN_KEY).ok()),
         }
         .into()
     }
@@ -23,6 +58,11 @@ impl Reducible for Token {
 
 pub type TokenContext = UseReducerHandle<Token>;
 
+/// The stored refresh token, if signed in.
+pub fn refresh_token() -> Option<String> {
+    LocalStorage::get::<String>(REFRESH_TOKEN_KEY).ok()
+}
+
 #[derive(Properties, Debug, PartialEq)]
 pub struct TokenProviderProps {
     #[prop_or_default]
@@ -32,7 +72,7 @@ pub struct TokenProviderProps {
 #[function_component]
 pub fn TokenProvider(props: &TokenProviderProps) -> Html {
     let token = use_reducer(|| {
-        let token_result = LocalStorage::get::<String>("token");
+        let token_result = LocalStorage::get::<String>(TOKEN_KEY);
 
         match token_result {
             Ok(token) => Token(Some(token)),
@@ -42,9 +82,124 @@ pub fn TokenProvider(props: &TokenProviderProps) -> Html {
         }
     });
 
+    // Other tabs refreshing or signing out change the shared storage, which
+    // this tab follows instead of refreshing with a rotated token.
+    use_effect_with((), {
+        let token = token.clone();
+
+        move |_| {
+            let listener = EventListener::new(&window(), "storage", move |event| {
+                let key = event.dyn_ref::<StorageEvent>().and_then(StorageEvent::key);
+
+                // No key means the whole storage was cleared.
+                if key.is_none_or(|key| key == TOKEN_KEY) {
+                    token.dispatch(TokenAction::Reload);
+                }
+            });
+
+            move || drop(listener)
+        }
+    });
+
+    // Every new access token schedules its own refresh shortly before it
+    // expires. A token that cannot be read, such as one stored before tokens
+    // expired, is refreshed straight away.
+    use_effect_with(token.0.clone(), {
+        let token = token.clone();
+
+        move |maybe_access_token| {
+            let maybe_timeout = maybe_access_token.clone().map(|access_token| {
+                let refresh_in = token_claims(&access_token)
+                    .map(|claims| claims.exp as f64 * 1000.0 - Date::now() - REFRESH_MARGIN_MS)
+                    .unwrap_or_default()
+                    .clamp(0.0, MAX_TIMEOUT_MS);
+
+                Timeout::new(refresh_in as u32, move || {
+                    wasm_bindgen_futures::spawn_local(async move {
+                        refresh_tokens(&token, &access_token).await;
+                    });
+                })
+            });
+
+            move || drop(maybe_timeout)
+        }
+    });
+
     html! {
         <ContextProvider<TokenContext> context={token}>
             {props.children.clone()}
         </ContextProvider<TokenContext>>
     }
 }
+
+/// Sends a request built with the access token, and if it is rejected as
+/// expired or revoked, refreshes the tokens and sends it once more.
+pub async fn send_with_token(
+    token: &TokenContext,
+    access_token: String,
+    request: impl Fn(&str) -> Result<Request, gloo::net::Error>,
+) -> Result<Response, gloo::net::Error> {
+    let response = request(&access_token)?.send().await?;
+
+    if response.status() != 401 {
+        return Ok(response);
+    }
+
+    match refresh_tokens(token, &access_token).await {
+        Some(access_token) => request(&access_token)?.send().await,
+        None => Ok(response),
+    }
+}
+
+/// Replaces `access_token` with a fresh one, returning it.
+///
+/// Tabs share their tokens through storage, and refresh tokens can only be
+/// used once, so tokens another tab already rotated are adopted rather than
+/// refreshed again. The tokens are only cleared when the refresh token that
+/// was rejected is still the stored one.
+async fn refresh_tokens(token: &TokenContext, access_token: &str) -> Option<String> {
+    let stored_access_token = LocalStorage::get::<String>(TOKEN_KEY).ok();
+
+    if stored_access_token.as_deref() != Some(access_token) {
+        token.dispatch(TokenAction::Reload);
+        return stored_access_token;
+    }
+
+    let Some(refresh_token) = refresh_token() else {
+        token.dispatch(TokenAction::Clear);
+        return None;
+    };
+
+    let refresh_token_request = RefreshTokenRequest {
+        refresh_token: refresh_token.clone(),
+    };
+
+    // A network error keeps the current tokens, the next request that fails
+    // with 401 tries again.
+    let refresh_response = Request::post("/api/token/refresh")
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(&refresh_token_request).unwrap())
+        .unwrap()
+        .send()
+        .await
+        .ok()?;
+
+    if refresh_response.ok()
+        && let Ok(token_response) = refresh_response.json::<TokenResponse>().await
+    {
+        let access_token = token_response.token.clone();
+        token.dispatch(TokenAction::Set(token_response));
+
+        Some(access_token)
+    } else if refresh_response.status() == 401 {
+        if self::refresh_token().as_ref() == Some(&refresh_token) {
+            token.dispatch(TokenAction::Clear);
+            None
+        } else {
+            token.dispatch(TokenAction::Reload);
+            LocalStorage::get(TOKEN_KEY).ok()
+        }
+    } else {
+        None
+    }
+}
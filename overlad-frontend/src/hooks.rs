@@ -1,5 +1,12 @@
+use gloo::net::http::Request;
+use overlad_api::User;
 use web_sys::window;
-use yew::{hook, use_effect_with};
+use yew::{hook, use_context, use_effect_with, use_state};
+
+use crate::{
+    components::token_provider::{TokenContext, send_with_token},
+    util::WithToken,
+};
 
 #[hook]
 pub fn use_scroll_to_top() {
@@ -8,3 +15,70 @@ pub fn use_scroll_to_top() {
         window.scroll_to_with_x_and_y(0.0, 0.0);
     });
 }
+
+/// The widths the backend keeps renditions at, narrowest first, or `None`
+/// until they are fetched. Failing to fetch them leaves none, so images are
+/// requested at full size.
+#[hook]
+pub fn use_rendition_widths() -> Option<Vec<u32>> {
+    let widths_state = use_state(Option::<Vec<u32>>::default);
+
+    use_effect_with((), {
+        let widths_state = widths_state.clone();
+
+        move |_| {
+            wasm_bindgen_futures::spawn_local(async move {
+                let widths = match Request::get("/api/renditions").send().await {
+                    Ok(widths_response) if widths_response.ok() => {
+                        widths_response.json::<Vec<u32>>().await.unwrap_or_default()
+                    }
+                    _ => Vec::new(),
+                };
+
+                widths_state.set(Some(widths));
+            });
+        }
+    });
+
+    (*widths_state).clone()
+}
+
+/// The signed in user, fetched from `/api/me` whenever the token changes.
+///
+/// A rejected token is refreshed and the request retried, leaving this `None`
+/// if that fails too.
+#[hook]
+pub fn use_me() -> Option<User> {
+    let token_context = use_context::<TokenContext>().expect("no token context found");
+    let me_state = use_state(Option::<User>::default);
+
+    use_effect_with(token_context.0.clone(), {
+        let me_state = me_state.clone();
+
+        let token_context = token_context.clone();
+
+        move |maybe_token| {
+            let me_state = me_state.clone();
+
+            if let Some(token) = maybe_token.clone() {
+                wasm_bindgen_futures::spawn_local(async move {
+                    let me_response = send_with_token(&token_context, token, |token| {
+                        Request::get("/api/me").with_token(token).build()
+                    })
+                    .await
+                    .unwrap();
+
+                    if me_response.ok() {
+                        me_state.set(me_response.json::<User>().await.ok());
+                    } else {
+                        me_state.set(None);
+                    }
+                });
+            } else {
+                me_state.set(None);
+            }
+        }
+    });
+
+    (*me_state).clone()
+}
@@ -1,7 +1,9 @@
 use gloo::net::http::Request;
 use image::{Rgba, RgbaImage, imageops::FilterType};
+use overlad_api::{DEFAULT_FONT, Image};
+use overlad_lib::{HorizontalAnchor, TextAlign, TextLayer, TextScale, VerticalAnchor};
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{js_sys::encode_uri, wasm_bindgen::JsCast, window, HtmlInputElement};
+use web_sys::{js_sys::encode_uri_component, wasm_bindgen::JsCast, window, HtmlInputElement, HtmlSelectElement, HtmlTextAreaElement};
 use yew::prelude::*;
 use yew_nav::use_hide_nav_menu;
 
@@ -11,6 +13,7 @@ use crate::{
         client_overlay::ClientOverlay,
     },
     hooks::use_scroll_to_top,
+    util::image_summary,
 };
 
 #[derive(Properties, PartialEq)]
@@ -18,17 +21,37 @@ pub struct ImagePageProps {
     pub id: String,
 }
 
+fn update_layer(
+    layers_state: &UseStateHandle<Vec<TextLayer>>,
+    index: usize,
+    update: impl FnOnce(&mut TextLayer),
+) {
+    let mut layers = (**layers_state).clone();
+
+    if let Some(layer) = layers.get_mut(index) {
+        update(layer);
+    }
+
+    layers_state.set(layers);
+}
+
 #[function_component]
 pub fn ImagePage(ImagePageProps { id }: &ImagePageProps) -> Html {
     use_hide_nav_menu(());
     use_scroll_to_top();
 
     let image_state = use_state(Option::<RgbaImage>::default);
-    let text_state = use_state(String::default);
-    let text_color_state = use_state(|| Rgba([255, 255, 255, 255]));
-    let text_scale_state = use_state(|| 1.0f64);
-    let outline_color_state = use_state(|| Rgba([0, 0, 0, 255]));
-    let outline_thickness_state = use_state(|| 0.0f64);
+    let image_info_state = use_state(Option::<Image>::default);
+    let layers_state = use_state(|| vec![TextLayer::default()]);
+    let selected_layer_state = use_state(|| 0usize);
+    let fonts_state = use_state(|| vec![String::from(DEFAULT_FONT)]);
+    let font_state = use_state(|| AttrValue::Static(DEFAULT_FONT));
+    let format_state = use_state(String::default);
+
+    let selected_layer = layers_state
+        .get(*selected_layer_state)
+        .cloned()
+        .unwrap_or_default();
 
     use_effect_with((), {
         let id = id.clone();
@@ -55,21 +78,133 @@ pub fn ImagePage(ImagePageProps { id }: &ImagePageProps) -> Html {
         }
     });
 
+    use_effect_with((), {
+        let id = id.clone();
+        let image_info_state = image_info_state.clone();
+
+        move |_| {
+            let id = id.clone();
+            let image_info_state = image_info_state.clone();
+
+            wasm_bindgen_futures::spawn_local(async move {
+                let image_info_response = Request::get(&format!("/api/image/{id}"))
+                    .send()
+                    .await
+                    .unwrap();
+
+                if image_info_response.ok() {
+                    let image_info = image_info_response.json::<Image>().await.unwrap();
+
+                    image_info_state.set(Some(image_info));
+                }
+            });
+        }
+    });
+
+    use_effect_with((), {
+        let fonts_state = fonts_state.clone();
+
+        move |_| {
+            let fonts_state = fonts_state.clone();
+
+            wasm_bindgen_futures::spawn_local(async move {
+                let fonts_response = Request::get("/api/fonts").send().await.unwrap();
+
+                let fonts = fonts_response.json::<Vec<String>>().await.unwrap();
+
+                fonts_state.set(fonts);
+            });
+        }
+    });
+
+    let on_font_change = {
+        let font_state = font_state.clone();
+
+        Callback::from(move |event: Event| {
+            if let Some(select) = event
+                .target()
+                .and_then(|target| target.dyn_into::<HtmlSelectElement>().ok())
+            {
+                font_state.set(AttrValue::from(select.value()));
+            }
+        })
+    };
+
+    let on_format_change = {
+        let format_state = format_state.clone();
+
+        Callback::from(move |event: Event| {
+            if let Some(select) = event
+                .target()
+                .and_then(|target| target.dyn_into::<HtmlSelectElement>().ok())
+            {
+                format_state.set(select.value());
+            }
+        })
+    };
+
+    let on_selected_layer_change = {
+        let selected_layer_state = selected_layer_state.clone();
+
+        Callback::from(move |event: Event| {
+            if let Some(select) = event
+                .target()
+                .and_then(|target| target.dyn_into::<HtmlSelectElement>().ok())
+                && let Ok(selected_layer) = select.value().parse()
+            {
+                selected_layer_state.set(selected_layer);
+            }
+        })
+    };
+
+    let on_add_layer = {
+        let layers_state = layers_state.clone();
+        let selected_layer_state = selected_layer_state.clone();
+
+        Callback::from(move |_| {
+            let mut layers = (*layers_state).clone();
+            layers.push(TextLayer::default());
+
+            selected_layer_state.set(layers.len() - 1);
+            layers_state.set(layers);
+        })
+    };
+
+    let on_remove_layer = {
+        let layers_state = layers_state.clone();
+        let selected_layer_state = selected_layer_state.clone();
+
+        Callback::from(move |_| {
+            let mut layers = (*layers_state).clone();
+
+            if layers.len() > 1 {
+                layers.remove(*selected_layer_state);
+
+                selected_layer_state.set((*selected_layer_state).min(layers.len() - 1));
+                layers_state.set(layers);
+            }
+        })
+    };
+
     let on_text_input = {
-        let text_state = text_state.clone();
+        let layers_state = layers_state.clone();
+        let selected_layer_state = selected_layer_state.clone();
 
         Callback::from(move |event: InputEvent| {
-            if let Some(input) = event
+            if let Some(textarea) = event
                 .target()
-                .and_then(|target| target.dyn_into::<HtmlInputElement>().ok())
+                .and_then(|target| target.dyn_into::<HtmlTextAreaElement>().ok())
             {
-                text_state.set(input.value());
+                update_layer(&layers_state, *selected_layer_state, |layer| {
+                    layer.text = textarea.value();
+                });
             }
         })
     };
 
     let on_text_color_input = {
-        let text_color_state = text_color_state.clone();
+        let layers_state = layers_state.clone();
+        let selected_layer_state = selected_layer_state.clone();
 
         Callback::from(move |event: InputEvent| {
             if let Some(input) = event
@@ -81,26 +216,52 @@ pub fn ImagePage(ImagePageProps { id }: &ImagePageProps) -> Html {
                 let g = value[1];
                 let b = value[2];
 
-                text_color_state.set(Rgba([r, g, b, 255]));
+                update_layer(&layers_state, *selected_layer_state, |layer| {
+                    layer.text_color = Rgba([r, g, b, 255]);
+                });
             }
         })
     };
 
     let on_text_scale_input = {
-        let text_scale_state = text_scale_state.clone();
+        let layers_state = layers_state.clone();
+        let selected_layer_state = selected_layer_state.clone();
 
         Callback::from(move |event: InputEvent| {
             if let Some(input) = event
                 .target()
                 .and_then(|target| target.dyn_into::<HtmlInputElement>().ok())
             {
-                text_scale_state.set(input.value_as_number());
+                update_layer(&layers_state, *selected_layer_state, |layer| {
+                    layer.scale = TextScale::Fixed(input.value_as_number());
+                });
+            }
+        })
+    };
+
+    let on_auto_text_scale_change = {
+        let layers_state = layers_state.clone();
+        let selected_layer_state = selected_layer_state.clone();
+
+        Callback::from(move |event: Event| {
+            if let Some(input) = event
+                .target()
+                .and_then(|target| target.dyn_into::<HtmlInputElement>().ok())
+            {
+                update_layer(&layers_state, *selected_layer_state, |layer| {
+                    layer.scale = if input.checked() {
+                        TextScale::Auto { min: 0.2, max: 5.0 }
+                    } else {
+                        TextScale::default()
+                    };
+                });
             }
         })
     };
 
     let on_outline_color_input = {
-        let outline_color_state = outline_color_state.clone();
+        let layers_state = layers_state.clone();
+        let selected_layer_state = selected_layer_state.clone();
 
         Callback::from(move |event: InputEvent| {
             if let Some(input) = event
@@ -112,32 +273,151 @@ pub fn ImagePage(ImagePageProps { id }: &ImagePageProps) -> Html {
                 let g = value[1];
                 let b = value[2];
 
-                outline_color_state.set(Rgba([r, g, b, 255]));
+                update_layer(&layers_state, *selected_layer_state, |layer| {
+                    layer.outline_color = Rgba([r, g, b, 255]);
+                });
             }
         })
     };
 
     let on_outline_thickness_input = {
-        let outline_thickness_state = outline_thickness_state.clone();
+        let layers_state = layers_state.clone();
+        let selected_layer_state = selected_layer_state.clone();
+
+        Callback::from(move |event: InputEvent| {
+            if let Some(input) = event
+                .target()
+                .and_then(|target| target.dyn_into::<HtmlInputElement>().ok())
+            {
+                update_layer(&layers_state, *selected_layer_state, |layer| {
+                    layer.thickness = input.value_as_number();
+                });
+            }
+        })
+    };
+
+    let on_rotation_input = {
+        let layers_state = layers_state.clone();
+        let selected_layer_state = selected_layer_state.clone();
 
         Callback::from(move |event: InputEvent| {
             if let Some(input) = event
                 .target()
                 .and_then(|target| target.dyn_into::<HtmlInputElement>().ok())
             {
-                outline_thickness_state.set(input.value_as_number());
+                update_layer(&layers_state, *selected_layer_state, |layer| {
+                    layer.rotation = input.value_as_number();
+                });
+            }
+        })
+    };
+
+    let on_line_spacing_input = {
+        let layers_state = layers_state.clone();
+        let selected_layer_state = selected_layer_state.clone();
+
+        Callback::from(move |event: InputEvent| {
+            if let Some(input) = event
+                .target()
+                .and_then(|target| target.dyn_into::<HtmlInputElement>().ok())
+            {
+                update_layer(&layers_state, *selected_layer_state, |layer| {
+                    layer.layout.line_spacing = input.value_as_number();
+                });
+            }
+        })
+    };
+
+    let on_vertical_anchor_change = {
+        let layers_state = layers_state.clone();
+        let selected_layer_state = selected_layer_state.clone();
+
+        Callback::from(move |event: Event| {
+            if let Some(select) = event
+                .target()
+                .and_then(|target| target.dyn_into::<HtmlSelectElement>().ok())
+                && let Ok(vertical_anchor) = serde_plain::from_str(&select.value())
+            {
+                update_layer(&layers_state, *selected_layer_state, |layer| {
+                    layer.layout.vertical_anchor = vertical_anchor;
+                });
+            }
+        })
+    };
+
+    let on_horizontal_anchor_change = {
+        let layers_state = layers_state.clone();
+        let selected_layer_state = selected_layer_state.clone();
+
+        Callback::from(move |event: Event| {
+            if let Some(select) = event
+                .target()
+                .and_then(|target| target.dyn_into::<HtmlSelectElement>().ok())
+                && let Ok(horizontal_anchor) = serde_plain::from_str(&select.value())
+            {
+                update_layer(&layers_state, *selected_layer_state, |layer| {
+                    layer.layout.horizontal_anchor = horizontal_anchor;
+                });
+            }
+        })
+    };
+
+    let on_text_align_change = {
+        let layers_state = layers_state.clone();
+        let selected_layer_state = selected_layer_state.clone();
+
+        Callback::from(move |event: Event| {
+            if let Some(select) = event
+                .target()
+                .and_then(|target| target.dyn_into::<HtmlSelectElement>().ok())
+                && let Ok(text_align) = serde_plain::from_str(&select.value())
+            {
+                update_layer(&layers_state, *selected_layer_state, |layer| {
+                    layer.layout.text_align = text_align;
+                });
             }
         })
     };
 
+    // Each key and value is encoded on its own, so text containing `&`, `=`,
+    // `#` or `+` survives the round trip.
+    let mut params = vec![(String::from("font"), font_state.to_string())];
+
+    for (index, layer) in layers_state.iter().enumerate() {
+        let mut push = |name: &str, value: String| params.push((format!("{name}[{index}]"), value));
+
+        push("text", layer.text.clone());
+        push("text_color", hex::encode(layer.text_color.0));
+        push(
+            "text_scale",
+            match layer.scale {
+                TextScale::Fixed(scale) => scale.to_string(),
+                TextScale::Auto { .. } => String::from("auto"),
+            },
+        );
+        push("outline_color", hex::encode(layer.outline_color.0));
+        push("outline_thickness", layer.thickness.to_string());
+        push("rotation", layer.rotation.to_string());
+        push("vertical_anchor", serde_plain::to_string(&layer.layout.vertical_anchor).unwrap());
+        push("horizontal_anchor", serde_plain::to_string(&layer.layout.horizontal_anchor).unwrap());
+        push("text_align", serde_plain::to_string(&layer.layout.text_align).unwrap());
+        push("line_spacing", layer.layout.line_spacing.to_string());
+    }
+
+    let query = params
+        .iter()
+        .map(|(key, value)| format!("{}={}", String::from(encode_uri_component(key)), String::from(encode_uri_component(value))))
+        .collect::<Vec<String>>()
+        .join("&");
+
     let link = format!(
-        "{}/api/overlay/{id}?text={}&text_color={}&text_scale={}&outline_color={}&outline_thickness={}",
+        "{}/api/overlay/{id}{}?{query}",
         window().unwrap().location().origin().unwrap(),
-        &*text_state,
-        hex::encode(text_color_state.0),
-        *text_scale_state,
-        hex::encode(outline_color_state.0),
-        *outline_thickness_state
+        if format_state.is_empty() {
+            String::new()
+        } else {
+            format!(".{}", *format_state)
+        },
     );
 
     let on_copy_link = {
@@ -147,8 +427,7 @@ pub fn ImagePage(ImagePageProps { id }: &ImagePageProps) -> Html {
             let link = link.clone();
 
             wasm_bindgen_futures::spawn_local(async move {
-                let url_encoded_link = encode_uri(&link).as_string().unwrap();
-                JsFuture::from(window().unwrap().navigator().clipboard().write_text(&url_encoded_link))
+                JsFuture::from(window().unwrap().navigator().clipboard().write_text(&link))
                     .await
                     .unwrap();
             });
@@ -161,30 +440,106 @@ pub fn ImagePage(ImagePageProps { id }: &ImagePageProps) -> Html {
                 if let Some(image) = &*image_state {
                     <ClientOverlay
                         image={image.clone()}
-                        text={(*text_state).clone()}
-                        text_color={*text_color_state}
-                        text_scale={*text_scale_state}
-                        outline_color={*outline_color_state}
-                        outline_thickness={*outline_thickness_state}
+                        layers={(*layers_state).clone()}
+                        font={(*font_state).clone()}
                         classes="border max-w-128 max-h-128"
                     />
                 }
-                <input value={(*text_state).clone()} oninput={on_text_input} class="bg-transparent text-gray-900 outline-blue-500 autofill:bg-blue-200 autofill:filter-none outline-offset-1 focus:outline-1 border p-1 rounded-sm" />
+                if let Some(image_info) = &*image_info_state {
+                    <p class="text-sm text-gray-500">
+                        if let Some(original_filename) = &image_info.original_filename {
+                            { format!("{original_filename} · ") }
+                        }
+                        { format!("{} · uploaded by {}", image_summary(image_info), image_info.user.username) }
+                    </p>
+                }
+                <div class="flex items-center">
+                    <label class="px-2 grow-0">{ "Font" }</label>
+                    <select onchange={on_font_change} class="grow outline-offset-1 focus:outline-1 border p-1 rounded-sm">
+                        {
+                            fonts_state.iter().map(|font| {
+                                html! {
+                                    <option value={font.clone()} selected={font.as_str() == font_state.as_str()}>{ font }</option>
+                                }
+                            }).collect::<Html>()
+                        }
+                    </select>
+                </div>
+                <div class="flex items-center gap-2">
+                    <select onchange={on_selected_layer_change} class="grow outline-offset-1 focus:outline-1 border p-1 rounded-sm">
+                        {
+                            (0..layers_state.len()).map(|index| {
+                                html! {
+                                    <option value={index.to_string()} selected={index == *selected_layer_state}>{ format!("Layer {}", index + 1) }</option>
+                                }
+                            }).collect::<Html>()
+                        }
+                    </select>
+                    <Button r#type={ButtonType::Button} onclick={on_add_layer}>{ "Add Layer" }</Button>
+                    <Button r#type={ButtonType::Button} onclick={on_remove_layer} disabled={layers_state.len() <= 1}>{ "Remove Layer" }</Button>
+                </div>
+                <textarea value={selected_layer.text.clone()} oninput={on_text_input} rows="2" class="bg-transparent text-gray-900 outline-blue-500 autofill:bg-blue-200 autofill:filter-none outline-offset-1 focus:outline-1 border p-1 rounded-sm" />
                 <div class="flex items-center">
                     <label class="px-2 grow-0">{ "Text Color" }</label>
-                    <input type="color" value={format!("#{}", hex::encode(&text_color_state.0[0..3]))} oninput={on_text_color_input} class="grow outline-offset-1 focus:outline-1 border rounded-sm" />
+                    <input type="color" value={format!("#{}", hex::encode(&selected_layer.text_color.0[0..3]))} oninput={on_text_color_input} class="grow outline-offset-1 focus:outline-1 border rounded-sm" />
                 </div>
                 <div class="flex items-center">
                     <label class="px-2 grow-0">{ "Text Size" }</label>
-                    <input type="range" min="0.2" step="0.01" max="5" value={text_scale_state.to_string()} oninput={on_text_scale_input} class="grow" />
+                    <input type="range" min="0.2" step="0.01" max="5" value={match selected_layer.scale { TextScale::Fixed(scale) => scale.to_string(), TextScale::Auto { .. } => String::from("1") }} oninput={on_text_scale_input} disabled={matches!(selected_layer.scale, TextScale::Auto { .. })} class="grow" />
+                </div>
+                <div class="flex items-center">
+                    <label class="px-2 grow-0">{ "Auto Size" }</label>
+                    <input type="checkbox" checked={matches!(selected_layer.scale, TextScale::Auto { .. })} onchange={on_auto_text_scale_change} class="outline-offset-1 focus:outline-1" />
                 </div>
                 <div class="flex items-center">
                     <label class="px-2 grow-0">{ "Outline Color" }</label>
-                    <input type="color" value={format!("#{}", hex::encode(&outline_color_state.0[0..3]))} oninput={on_outline_color_input} class="grow outline-offset-1 focus:outline-1 border rounded-sm" />
+                    <input type="color" value={format!("#{}", hex::encode(&selected_layer.outline_color.0[0..3]))} oninput={on_outline_color_input} class="grow outline-offset-1 focus:outline-1 border rounded-sm" />
                 </div>
                 <div class="flex items-center">
                     <label class="px-2 grow-0">{ "Outline Thickness" }</label>
-                    <input type="range" min="0" step="1" max="10" value={outline_thickness_state.to_string()} oninput={on_outline_thickness_input} class="grow" />
+                    <input type="range" min="0" step="1" max="10" value={selected_layer.thickness.to_string()} oninput={on_outline_thickness_input} class="grow" />
+                </div>
+                <div class="flex items-center">
+                    <label class="px-2 grow-0">{ "Rotation" }</label>
+                    <input type="range" min="-180" step="1" max="180" value={selected_layer.rotation.to_string()} oninput={on_rotation_input} class="grow" />
+                </div>
+                <div class="flex items-center">
+                    <label class="px-2 grow-0">{ "Line Spacing" }</label>
+                    <input type="range" min="0.5" step="0.05" max="3" value={selected_layer.layout.line_spacing.to_string()} oninput={on_line_spacing_input} class="grow" />
+                </div>
+                <div class="flex items-center">
+                    <label class="px-2 grow-0">{ "Vertical Position" }</label>
+                    <select onchange={on_vertical_anchor_change} class="grow outline-offset-1 focus:outline-1 border p-1 rounded-sm">
+                        <option value="top" selected={selected_layer.layout.vertical_anchor == VerticalAnchor::Top}>{ "Top" }</option>
+                        <option value="center" selected={selected_layer.layout.vertical_anchor == VerticalAnchor::Center}>{ "Center" }</option>
+                        <option value="bottom" selected={selected_layer.layout.vertical_anchor == VerticalAnchor::Bottom}>{ "Bottom" }</option>
+                    </select>
+                </div>
+                <div class="flex items-center">
+                    <label class="px-2 grow-0">{ "Horizontal Position" }</label>
+                    <select onchange={on_horizontal_anchor_change} class="grow outline-offset-1 focus:outline-1 border p-1 rounded-sm">
+                        <option value="left" selected={selected_layer.layout.horizontal_anchor == HorizontalAnchor::Left}>{ "Left" }</option>
+                        <option value="center" selected={selected_layer.layout.horizontal_anchor == HorizontalAnchor::Center}>{ "Center" }</option>
+                        <option value="right" selected={selected_layer.layout.horizontal_anchor == HorizontalAnchor::Right}>{ "Right" }</option>
+                    </select>
+                </div>
+                <div class="flex items-center">
+                    <label class="px-2 grow-0">{ "Text Alignment" }</label>
+                    <select onchange={on_text_align_change} class="grow outline-offset-1 focus:outline-1 border p-1 rounded-sm">
+                        <option value="left" selected={selected_layer.layout.text_align == TextAlign::Left}>{ "Left" }</option>
+                        <option value="center" selected={selected_layer.layout.text_align == TextAlign::Center}>{ "Center" }</option>
+                        <option value="right" selected={selected_layer.layout.text_align == TextAlign::Right}>{ "Right" }</option>
+                    </select>
+                </div>
+                <div class="flex items-center">
+                    <label class="px-2 grow-0">{ "Link Format" }</label>
+                    <select onchange={on_format_change} class="grow outline-offset-1 focus:outline-1 border p-1 rounded-sm">
+                        <option value="" selected={format_state.is_empty()}>{ "Original" }</option>
+                        <option value="png" selected={*format_state == "png"}>{ "PNG" }</option>
+                        <option value="jpeg" selected={*format_state == "jpeg"}>{ "JPEG" }</option>
+                        <option value="gif" selected={*format_state == "gif"}>{ "GIF" }</option>
+                        <option value="webp" selected={*format_state == "webp"}>{ "WebP" }</option>
+                    </select>
                 </div>
                 <Button r#type={ButtonType::Button} onclick={on_copy_link}>{ "Copy Link" }</Button>
             </div>
@@ -1,6 +1,5 @@
 use gloo::net::http::Request;
-use gloo::storage::{LocalStorage, Storage};
-use overlad_api::TokenRequest;
+use overlad_api::{TokenRequest, TokenResponse};
 use web_sys::HtmlInputElement;
 use yew::prelude::*;
 use yew_nav::use_hide_nav_menu;
@@ -13,6 +12,7 @@ use crate::{
         button::{Button, ButtonType},
         token_provider::{TokenAction, TokenContext},
     },
+    util::error_message,
 };
 
 #[function_component]
@@ -88,15 +88,14 @@ pub fn LoginPage() -> Html {
                     .await
                     .unwrap();
 
-                let token_response_text = token_response.text().await.unwrap();
-
                 if token_response.ok() {
-                    LocalStorage::set("token", &token_response_text).unwrap();
+                    let token_response = token_response.json::<TokenResponse>().await.unwrap();
 
-                    token_context.dispatch(TokenAction::Set(token_response_text));
+                    token_context.dispatch(TokenAction::Set(token_response));
                     navigator.push(&Route::Root);
                 } else {
-                    error_text.set(Some(token_response_text));
+                    let token_response_text = token_response.text().await.unwrap();
+                    error_text.set(Some(error_message(token_response_text)));
                 }
             });
         })
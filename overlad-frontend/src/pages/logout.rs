@@ -1,10 +1,11 @@
-use gloo::storage::{LocalStorage, Storage};
+use gloo::net::http::Request;
+use overlad_api::RefreshTokenRequest;
 use yew::prelude::*;
 use yew_router::hooks::use_navigator;
 
 use crate::{
     Route,
-    components::token_provider::{TokenAction, TokenContext},
+    components::token_provider::{TokenAction, TokenContext, refresh_token},
 };
 
 #[function_component]
@@ -12,9 +13,24 @@ pub fn LogoutPage() -> Html {
     let navigator = use_navigator().unwrap();
     let token_context = use_context::<TokenContext>().expect("no token found");
 
-    token_context.dispatch(TokenAction::Clear);
-    LocalStorage::delete("token");
-    navigator.replace(&Route::Root);
+    use_effect_with((), move |_| {
+        // Revoking is best effort, the tokens are forgotten either way.
+        if let Some(refresh_token) = refresh_token() {
+            let refresh_token_request = RefreshTokenRequest { refresh_token };
+
+            wasm_bindgen_futures::spawn_local(async move {
+                let _ = Request::post("/api/logout")
+                    .header("Content-Type", "application/json")
+                    .body(serde_json::to_string(&refresh_token_request).unwrap())
+                    .unwrap()
+                    .send()
+                    .await;
+            });
+        }
+
+        token_context.dispatch(TokenAction::Clear);
+        navigator.replace(&Route::Root);
+    });
 
     html! {}
 }
@@ -1,8 +1,7 @@
-use gloo::{
-    net::http::Request,
-    storage::{LocalStorage, Storage},
-};
-use overlad_api::{RegisterRequest, TokenRequest};
+use std::collections::BTreeMap;
+
+use gloo::net::http::Request;
+use overlad_api::{RegisterRequest, TokenRequest, TokenResponse};
 use web_sys::HtmlInputElement;
 use yew::prelude::*;
 use yew_nav::use_hide_nav_menu;
@@ -15,6 +14,7 @@ use crate::{
         token_provider::{TokenAction, TokenContext},
     },
     hooks::use_scroll_to_top,
+    util::{error_message, error_response},
 };
 
 #[function_component]
@@ -30,6 +30,7 @@ pub fn RegisterPage() -> Html {
     let confirm_password_input_node_ref = use_node_ref();
 
     let error_text = use_state::<Option<String>, _>(|| None);
+    let field_errors = use_state(BTreeMap::<String, String>::new);
 
     let username = use_state(String::default);
     let password = use_state(String::default);
@@ -76,6 +77,7 @@ pub fn RegisterPage() -> Html {
 
     let handle_submit = {
         let error_text = error_text.clone();
+        let field_errors = field_errors.clone();
 
         let username = username.clone();
         let password = password.clone();
@@ -86,6 +88,7 @@ pub fn RegisterPage() -> Html {
             let token_context = token_context.clone();
 
             let error_text = error_text.clone();
+            let field_errors = field_errors.clone();
 
             let username = username.clone();
             let password = password.clone();
@@ -109,6 +112,8 @@ pub fn RegisterPage() -> Html {
                     .unwrap();
 
                 if register_response.ok() {
+                    field_errors.set(BTreeMap::new());
+
                     let token_request = TokenRequest {
                         username: (*username).clone(),
                         password: (*password).clone(),
@@ -122,19 +127,27 @@ pub fn RegisterPage() -> Html {
                         .await
                         .unwrap();
 
-                    let token_response_text = token_response.text().await.unwrap();
-
                     if token_response.ok() {
-                        LocalStorage::set("token", &token_response_text).unwrap();
+                        let token_response = token_response.json::<TokenResponse>().await.unwrap();
 
-                        token_context.dispatch(TokenAction::Set(token_response_text));
+                        token_context.dispatch(TokenAction::Set(token_response));
                         navigator.push(&Route::Root);
                     } else {
-                        error_text.set(Some(token_response_text));
+                        let token_response_text = token_response.text().await.unwrap();
+                        error_text.set(Some(error_message(token_response_text)));
                     }
                 } else {
                     let register_response_text = register_response.text().await.unwrap();
-                    error_text.set(Some(register_response_text));
+                    let error_response = error_response(register_response_text);
+
+                    // Field problems are shown next to their inputs instead.
+                    error_text.set(
+                        error_response
+                            .fields
+                            .is_empty()
+                            .then_some(error_response.error),
+                    );
+                    field_errors.set(error_response.fields);
                 }
             });
         })
@@ -155,6 +168,9 @@ pub fn RegisterPage() -> Html {
                     placeholder="Username"
                     required=true
                 />
+                if let Some(field_error) = field_errors.get("username") {
+                    <p class="text-sm text-red-500">{field_error}</p>
+                }
                 <input
                     ref={password_input_node_ref}
                     class="bg-transparent text-gray-900 outline-blue-500 outline-offset-1 focus:outline-1 border p-1 rounded-sm"
@@ -164,6 +180,9 @@ pub fn RegisterPage() -> Html {
                     placeholder="Password"
                     required=true
                 />
+                if let Some(field_error) = field_errors.get("password") {
+                    <p class="text-sm text-red-500">{field_error}</p>
+                }
                 <input
                     ref={confirm_password_input_node_ref}
                     class="bg-transparent text-gray-900 outline-blue-500 outline-offset-1 focus:outline-1 border p-1 rounded-sm"
@@ -173,6 +192,9 @@ pub fn RegisterPage() -> Html {
                     placeholder="Confirm Password"
                     required=true
                 />
+                if let Some(field_error) = field_errors.get("confirm_password") {
+                    <p class="text-sm text-red-500">{field_error}</p>
+                }
                 <Button r#type={ButtonType::Submit}>
                     { "Register" }
                 </Button>
@@ -1,5 +1,6 @@
 use gloo::{net::http::Request, utils::window};
 use image::RgbaImage;
+use overlad_lib::{TextLayer, TextScale};
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{js_sys::encode_uri, wasm_bindgen::JsCast, HtmlInputElement};
 use yew::prelude::*;
@@ -64,6 +65,13 @@ pub fn RootPage() -> Html {
     let text_scale = 2.0;
     let outline_thickness = 1.0;
 
+    let layers = vec![TextLayer {
+        text: (*text_state).clone(),
+        scale: TextScale::Fixed(text_scale),
+        thickness: outline_thickness,
+        ..TextLayer::default()
+    }];
+
     let link = format!(
         "{}/api/overlay/{}?text={}&text_scale={}&outline_thickness={}",
         window().location().origin().unwrap(),
@@ -132,7 +140,7 @@ pub fn RootPage() -> Html {
                     <div class="flex justify-center items-center">
                         <div class="flex flex-col gap-2">
                             if let Some(example_image) = &*example_image_state {
-                                <ClientOverlay image={example_image.clone()} text={(*text_state).clone()} text_scale={text_scale} outline_thickness={outline_thickness} classes="max-h-64 border" />
+                                <ClientOverlay image={example_image.clone()} layers={layers.clone()} classes="max-h-64 border" />
                             }
                             <input class="bg-transparent text-gray-900 outline-blue-500 outline-offset-1 focus:outline-1 border p-1 rounded-sm" type="text" value={(*text_state).clone()} oninput={on_text_input} />
                         </div>
@@ -148,7 +156,7 @@ pub fn RootPage() -> Html {
                     <div class="flex justify-center items-center">
                         <div class="flex flex-col gap-2">
                             if let Some(example_image) = &*example_image_state {
-                                <ClientOverlay image={example_image.clone()} text={(*text_state).clone()} text_scale={text_scale} outline_thickness={outline_thickness} classes="max-h-64 border" />
+                                <ClientOverlay image={example_image.clone()} layers={layers.clone()} classes="max-h-64 border" />
                             }
                             <Button r#type={ButtonType::Button} onclick={copy_link}>{ "Copy" }</Button>
                         </div>
@@ -1,6 +1,6 @@
 use gloo::net::http::Request;
 use overlad_api::Image;
-use web_sys::{File, FormData, HtmlInputElement, Url, wasm_bindgen::JsCast};
+use web_sys::{File, FormData, HtmlInputElement, HtmlSelectElement, Url, wasm_bindgen::JsCast};
 use yew::prelude::*;
 use yew_nav::use_hide_nav_menu;
 use yew_router::hooks::use_navigator;
@@ -9,10 +9,10 @@ use crate::{
     Route,
     components::{
         button::{Button, ButtonType},
-        token_provider::TokenContext,
+        token_provider::{TokenContext, send_with_token},
     },
     hooks::use_scroll_to_top,
-    util::WithToken,
+    util::{WithToken, error_message},
 };
 
 #[function_component]
@@ -25,6 +25,7 @@ pub fn UploadPage() -> Html {
 
     let error_text_state = use_state(Option::<String>::default);
     let file_state = use_state(Option::<File>::default);
+    let rotate_state = use_state(String::new);
 
     let preview_url_memo = use_memo(file_state.clone(), |file_state| {
         file_state
@@ -40,6 +41,7 @@ pub fn UploadPage() -> Html {
             let error_text_state = error_text_state.clone();
             let file_state = file_state.clone();
 
+            #[allow(clippy::collapsible_if)]
             if let Some(file_input) = event
                 .target()
                 .and_then(|target| target.dyn_into::<HtmlInputElement>().ok())
@@ -61,13 +63,28 @@ pub fn UploadPage() -> Html {
         })
     };
 
+    let handle_rotate_change = {
+        let rotate_state = rotate_state.clone();
+
+        Callback::from(move |event: Event| {
+            if let Some(select) = event
+                .target()
+                .and_then(|target| target.dyn_into::<HtmlSelectElement>().ok())
+            {
+                rotate_state.set(select.value());
+            }
+        })
+    };
+
     let handle_submit = {
         let navigator = navigator.clone();
+        let rotate_state = rotate_state.clone();
         let token_context = token_context.clone();
         let error_text_state = error_text_state.clone();
 
         Callback::from(move |event: SubmitEvent| {
             let navigator = navigator.clone();
+            let token_context = token_context.clone();
             let error_text_state = error_text_state.clone();
 
             event.prevent_default();
@@ -75,17 +92,21 @@ pub fn UploadPage() -> Html {
             if let Some(token) = token_context.0.clone()
                 && let Some(file) = (*file_state).clone()
             {
+                let rotate = (*rotate_state).clone();
+
                 wasm_bindgen_futures::spawn_local(async move {
                     let form = FormData::new().unwrap();
                     form.append_with_blob("image", &file).unwrap();
 
-                    let image_response = Request::post("/api/upload")
-                        .with_token(token)
-                        .body(form)
-                        .unwrap()
-                        .send()
-                        .await
-                        .unwrap();
+                    if !rotate.is_empty() {
+                        form.append_with_str("rotate", &rotate).unwrap();
+                    }
+
+                    let image_response = send_with_token(&token_context, token, |token| {
+                        Request::post("/api/upload").with_token(token).body(&form)
+                    })
+                    .await
+                    .unwrap();
 
                     if image_response.ok() {
                         let image = image_response.json::<Image>().await.unwrap();
@@ -94,7 +115,7 @@ pub fn UploadPage() -> Html {
                     } else {
                         let image_response_text = image_response.text().await.unwrap();
 
-                        error_text_state.set(Some(image_response_text));
+                        error_text_state.set(Some(error_message(image_response_text)));
                     }
                 });
             }
@@ -111,6 +132,15 @@ pub fn UploadPage() -> Html {
                     accept="image/*"
                     required=true
                 />
+                <div class="flex items-center">
+                    <label class="pr-2 grow-0">{ "Rotate" }</label>
+                    <select onchange={handle_rotate_change} class="outline-offset-1 focus:outline-1 border p-1 rounded-sm">
+                        <option value="" selected={rotate_state.is_empty()}>{ "None" }</option>
+                        <option value="90" selected={*rotate_state == "90"}>{ "90° clockwise" }</option>
+                        <option value="180" selected={*rotate_state == "180"}>{ "180°" }</option>
+                        <option value="270" selected={*rotate_state == "270"}>{ "90° counterclockwise" }</option>
+                    </select>
+                </div>
                 if let Some(error_text) = &*error_text_state {
                     <p class="text-red-500">{error_text}</p>
                 }
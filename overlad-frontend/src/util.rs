@@ -1,4 +1,8 @@
+use std::collections::BTreeMap;
+
 use gloo::net::http::RequestBuilder;
+use jwt::{Header, Token, Unverified};
+use overlad_api::{ErrorResponse, Image, TokenClaims};
 
 pub trait WithToken {
     fn with_token(self, token: impl AsRef<str>) -> Self;
@@ -9,3 +13,55 @@ impl WithToken for RequestBuilder {
         self.header("Authorization", &format!("Bearer {}", token.as_ref()))
     }
 }
+
+/// Reads the claims of an access token without verifying it, which only the
+/// backend can do.
+pub fn token_claims(token: &str) -> Option<TokenClaims> {
+    Token::<Header, TokenClaims, Unverified<'_>>::parse_unverified(token)
+        .ok()
+        .map(|token| token.claims().clone())
+}
+
+/// Parses an error response body, treating a body that is not an
+/// [`ErrorResponse`] as the message itself.
+pub fn error_response(response_text: String) -> ErrorResponse {
+    serde_json::from_str::<ErrorResponse>(&response_text).unwrap_or(ErrorResponse {
+        error: response_text,
+        fields: BTreeMap::new(),
+    })
+}
+
+/// Extracts the message from an error response body.
+pub fn error_message(response_text: String) -> String {
+    error_response(response_text).error
+}
+
+/// Formats a byte count with the largest unit that keeps it at or above 1.
+pub fn format_size(size_bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+
+    let mut size = size_bytes as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{size_bytes} {}", UNITS[unit])
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// A one line description of an image's dimensions, size and upload date.
+pub fn image_summary(image: &Image) -> String {
+    format!(
+        "{}×{} · {} · {}",
+        image.width,
+        image.height,
+        format_size(image.size_bytes),
+        image.created_at.format("%Y-%m-%d"),
+    )
+}
@@ -0,0 +1,192 @@
+use std::io::Cursor;
+
+use image::{
+    AnimationDecoder, Frame, ImageDecoder, ImageError, ImageFormat, ImageResult, Limits,
+    codecs::{
+        gif::{GifDecoder, GifEncoder, Repeat},
+        webp::WebPDecoder,
+    },
+    error::{LimitError, LimitErrorKind},
+};
+
+/// Caps on decoding an animation, so a long or large one is rejected before
+/// all of its frames are held in memory.
+#[derive(Debug, Clone)]
+pub struct AnimationLimits {
+    /// Limits of the decoder, which apply to the canvas and each frame.
+    pub decoder: Limits,
+    pub max_frames: usize,
+
+    /// Most pixels across all frames, each of which covers the full canvas.
+    pub max_total_pixels: u64,
+}
+
+/// Decodes every frame of an animated GIF or WebP, or returns `None` when the
+/// image is a still.
+///
+/// Frames are composited onto the full canvas, so each one can be drawn on
+/// independently.
+pub fn decode_animation(bytes: &[u8], limits: &AnimationLimits) -> ImageResult<Option<Vec<Frame>>> {
+    let Some(frames) = frames(bytes, limits)? else {
+        return Ok(None);
+    };
+
+    let frames = frames.collect::<ImageResult<Vec<_>>>()?;
+
+    Ok((frames.len() > 1).then_some(frames))
+}
+
+/// Checks whether a GIF or WebP is animated, decoding every frame under
+/// `limits` without keeping them, so a huge animation is rejected instead of
+/// held in memory.
+pub fn is_animated(bytes: &[u8], limits: &AnimationLimits) -> ImageResult<bool> {
+    let Some(frames) = frames(bytes, limits)? else {
+        return Ok(false);
+    };
+
+    let mut frame_count = 0;
+
+    for frame in frames {
+        frame?;
+        frame_count += 1;
+    }
+
+    Ok(frame_count > 1)
+}
+
+/// The frames of a GIF or WebP decoded one at a time, failing with a limit
+/// error at the first one past `limits`, or `None` when the image cannot be
+/// animated.
+fn frames<'a>(
+    bytes: &'a [u8],
+    limits: &AnimationLimits,
+) -> ImageResult<Option<impl Iterator<Item = ImageResult<Frame>> + 'a>> {
+    let (frames, (width, height)) = match image::guess_format(bytes)? {
+        ImageFormat::Gif => {
+            let mut decoder = GifDecoder::new(Cursor::new(bytes))?;
+            decoder.set_limits(limits.decoder.clone())?;
+            let dimensions = decoder.dimensions();
+
+            (decoder.into_frames(), dimensions)
+        }
+        ImageFormat::WebP => {
+            let mut decoder = WebPDecoder::new(Cursor::new(bytes))?;
+            decoder.set_limits(limits.decoder.clone())?;
+
+            if !decoder.has_animation() {
+                return Ok(None);
+            }
+
+            let dimensions = decoder.dimensions();
+
+            (decoder.into_frames(), dimensions)
+        }
+        _ => return Ok(None),
+    };
+
+    let frame_pixels = (u64::from(width) * u64::from(height)).max(1);
+    let max_frames = usize::try_from(limits.max_total_pixels / frame_pixels)
+        .unwrap_or(usize::MAX)
+        .min(limits.max_frames);
+
+    Ok(Some(frames.enumerate().map(move |(index, frame)| {
+        if index < max_frames {
+            frame
+        } else {
+            Err(ImageError::Limits(LimitError::from_kind(
+                LimitErrorKind::DimensionError,
+            )))
+        }
+    })))
+}
+
+/// Encodes frames as an endlessly looping GIF, keeping each frame's delay.
+pub fn encode_gif(frames: Vec<Frame>) -> ImageResult<Vec<u8>> {
+    let mut buf = Vec::new();
+
+    {
+        let mut encoder = GifEncoder::new(&mut buf);
+        encoder.set_repeat(Repeat::Infinite)?;
+        encoder.encode_frames(frames)?;
+    }
+
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Delay, Rgba, RgbaImage};
+
+    use super::*;
+
+    fn gif(frame_count: u8) -> Vec<u8> {
+        let frames = (0..frame_count)
+            .map(|index| {
+                Frame::from_parts(
+                    RgbaImage::from_pixel(4, 2, Rgba([index * 10, 0, 0, 255])),
+                    0,
+                    0,
+                    Delay::from_numer_denom_ms(100, 1),
+                )
+            })
+            .collect();
+
+        encode_gif(frames).unwrap()
+    }
+
+    fn limits(max_frames: usize, max_total_pixels: u64) -> AnimationLimits {
+        AnimationLimits {
+            decoder: Limits::default(),
+            max_frames,
+            max_total_pixels,
+        }
+    }
+
+    fn is_limit_error<T>(result: ImageResult<T>) -> bool {
+        matches!(result, Err(ImageError::Limits(_)))
+    }
+
+    #[test]
+    fn animations_within_the_limits_are_decoded() {
+        let frames = decode_animation(&gif(3), &limits(3, 24)).unwrap().unwrap();
+
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[2].buffer().dimensions(), (4, 2));
+        assert!(is_animated(&gif(3), &limits(3, 24)).unwrap());
+    }
+
+    #[test]
+    fn single_frames_are_not_animations() {
+        assert!(decode_animation(&gif(1), &limits(1, 8)).unwrap().is_none());
+        assert!(!is_animated(&gif(1), &limits(1, 8)).unwrap());
+    }
+
+    #[test]
+    fn too_many_frames_are_rejected() {
+        assert!(is_limit_error(decode_animation(
+            &gif(3),
+            &limits(2, u64::MAX)
+        )));
+        assert!(is_limit_error(is_animated(&gif(3), &limits(2, u64::MAX))));
+    }
+
+    #[test]
+    fn too_many_pixels_across_frames_are_rejected() {
+        assert!(is_limit_error(decode_animation(
+            &gif(3),
+            &limits(usize::MAX, 23)
+        )));
+        assert!(is_limit_error(is_animated(
+            &gif(3),
+            &limits(usize::MAX, 23)
+        )));
+    }
+
+    #[test]
+    fn frames_are_decoded_under_the_decoder_limits() {
+        let mut limits = limits(usize::MAX, u64::MAX);
+        limits.decoder.max_image_width = Some(3);
+
+        assert!(is_limit_error(decode_animation(&gif(3), &limits)));
+    }
+}
@@ -0,0 +1,95 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::Path,
+};
+
+use ab_glyph::{FontArc, InvalidFont};
+
+const FONT_EXTENSIONS: [&str; 2] = ["ttf", "otf"];
+
+/// A parsed font together with the file it was loaded from.
+#[derive(Clone)]
+pub struct FontFile {
+    pub font: FontArc,
+    pub bytes: Vec<u8>,
+    pub extension: String,
+}
+
+/// Fonts available for rendering, looked up by name.
+#[derive(Clone, Default)]
+pub struct FontRegistry {
+    fonts: BTreeMap<String, FontFile>,
+}
+
+impl FontRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        bytes: Vec<u8>,
+        extension: impl Into<String>,
+    ) -> Result<(), InvalidFont> {
+        let font = FontArc::try_from_vec(bytes.clone())?;
+
+        self.fonts.insert(
+            name.into(),
+            FontFile {
+                font,
+                bytes,
+                extension: extension.into(),
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Registers every TTF and OTF file in `path` under its file stem, replacing
+    /// any font already registered with the same name. Errors name the file
+    /// that could not be loaded.
+    pub fn load_dir(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let with_path = |path: &Path, error: io::Error| {
+            io::Error::new(error.kind(), format!("{}: {error}", path.display()))
+        };
+
+        for entry in fs::read_dir(path).map_err(|error| with_path(path, error))? {
+            let path = entry.map_err(|error| with_path(path, error))?.path();
+
+            let Some(extension) = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .map(|extension| extension.to_ascii_lowercase())
+                .filter(|extension| FONT_EXTENSIONS.contains(&extension.as_str()))
+            else {
+                continue;
+            };
+
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            let bytes = fs::read(&path).map_err(|error| with_path(&path, error))?;
+
+            self.insert(name, bytes, extension).map_err(|error| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {error}", path.display()),
+                )
+            })?;
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&FontFile> {
+        self.fonts.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.fonts.keys().map(String::as_str)
+    }
+}
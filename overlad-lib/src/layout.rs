@@ -0,0 +1,299 @@
+use ab_glyph::{Font, PxScale, ScaleFont};
+use imageproc::drawing::text_size;
+
+use crate::TextAlign;
+
+/// A single laid out line, positioned relative to the top-left of its block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineBox {
+    pub text: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Wrapped and measured text, ready to be placed on an image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextBlock {
+    pub lines: Vec<LineBox>,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl TextBlock {
+    /// Wraps `text` to `max_width` and justifies each line within the block.
+    ///
+    /// Lines break at spaces, explicit newlines and, when a single word is wider
+    /// than `max_width`, between characters. Consecutive lines are advanced by
+    /// the font's line height (ascent - descent + line gap) times `line_spacing`.
+    pub fn layout(
+        text: &str,
+        scale: impl Into<PxScale> + Copy,
+        font: &impl Font,
+        max_width: f32,
+        line_spacing: f32,
+        text_align: TextAlign,
+    ) -> Self {
+        let scaled_font = font.as_scaled(scale);
+        let line_height = scaled_font.height();
+        let line_advance = (line_height + scaled_font.line_gap()) * line_spacing;
+
+        let wrapped_lines = wrap_lines(text, scale, font, max_width);
+
+        let measured_lines = wrapped_lines
+            .into_iter()
+            .map(|line| {
+                let width = measure(scale, font, &line);
+                (line, width)
+            })
+            .collect::<Vec<(String, f32)>>();
+
+        let width = measured_lines
+            .iter()
+            .map(|(_, width)| *width)
+            .fold(0.0, f32::max);
+
+        let lines = measured_lines
+            .into_iter()
+            .enumerate()
+            .map(|(index, (text, line_width))| {
+                let x = match text_align {
+                    TextAlign::Left => 0.0,
+                    TextAlign::Center => (width - line_width) / 2.0,
+                    TextAlign::Right => width - line_width,
+                };
+
+                LineBox {
+                    text,
+                    x,
+                    y: index as f32 * line_advance,
+                    width: line_width,
+                    height: line_height,
+                }
+            })
+            .collect::<Vec<LineBox>>();
+
+        let height = lines
+            .last()
+            .map(|line| line.y + line.height)
+            .unwrap_or_default();
+
+        Self {
+            lines,
+            width,
+            height,
+        }
+    }
+}
+
+/// Finds the largest font scale between `min_scale` and `max_scale` at which
+/// `text` wraps into a block no larger than `max_width` by `max_height`.
+///
+/// Returns `min_scale` when the text does not fit even at the smallest size.
+pub fn fit_scale(
+    text: &str,
+    font: &impl Font,
+    max_width: f32,
+    max_height: f32,
+    line_spacing: f32,
+    min_scale: f32,
+    max_scale: f32,
+) -> f32 {
+    let fits = |scale: f32| {
+        let block = TextBlock::layout(
+            text,
+            scale,
+            font,
+            max_width,
+            line_spacing,
+            TextAlign::Left,
+        );
+
+        block.width <= max_width && block.height <= max_height
+    };
+
+    if fits(max_scale) {
+        return max_scale;
+    }
+
+    let (mut low, mut high) = (min_scale, max_scale);
+    while high - low > 0.5 {
+        let mid = (low + high) / 2.0;
+
+        if fits(mid) {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    low
+}
+
+/// Breaks `text` into lines no wider than `max_width`.
+pub fn wrap_lines(
+    text: &str,
+    scale: impl Into<PxScale> + Copy,
+    font: &impl Font,
+    max_width: f32,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.lines() {
+        let mut current_line = String::new();
+
+        for word in paragraph.split(' ') {
+            let candidate = if current_line.is_empty() {
+                word.to_owned()
+            } else {
+                format!("{current_line} {word}")
+            };
+
+            if measure(scale, font, &candidate) <= max_width {
+                current_line = candidate;
+                continue;
+            }
+
+            if !current_line.is_empty() {
+                lines.push(std::mem::take(&mut current_line));
+            }
+
+            for c in word.chars() {
+                current_line.push(c);
+
+                if measure(scale, font, &current_line) > max_width && current_line.chars().count() > 1
+                {
+                    current_line.pop();
+                    lines.push(std::mem::take(&mut current_line));
+                    current_line.push(c);
+                }
+            }
+        }
+
+        lines.push(current_line);
+    }
+
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+
+    lines
+}
+
+fn measure(scale: impl Into<PxScale> + Copy, font: &impl Font, text: &str) -> f32 {
+    text_size(scale, font, text).0 as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use ab_glyph::FontRef;
+
+    use super::*;
+
+    const SCALE: f32 = 32.0;
+
+    fn font() -> FontRef<'static> {
+        FontRef::try_from_slice(include_bytes!("../../roboto.ttf")).unwrap()
+    }
+
+    fn wrap(text: &str, max_width: f32) -> Vec<String> {
+        wrap_lines(text, SCALE, &font(), max_width)
+    }
+
+    #[test]
+    fn wrap_lines_keeps_text_that_fits_on_one_line() {
+        assert_eq!(wrap("one two three", 10_000.0), ["one two three"]);
+    }
+
+    #[test]
+    fn wrap_lines_breaks_at_spaces() {
+        let max_width = measure(SCALE, &font(), "one two");
+
+        assert_eq!(wrap("one two three", max_width), ["one two", "three"]);
+    }
+
+    #[test]
+    fn wrap_lines_breaks_at_explicit_newlines() {
+        assert_eq!(wrap("one\ntwo\r\nthree", 10_000.0), ["one", "two", "three"]);
+    }
+
+    #[test]
+    fn wrap_lines_keeps_empty_lines() {
+        assert_eq!(wrap("one\n\nthree", 10_000.0), ["one", "", "three"]);
+        assert_eq!(wrap("", 10_000.0), [""]);
+    }
+
+    #[test]
+    fn wrap_lines_breaks_long_words_between_characters() {
+        let font = font();
+        let max_width = measure(SCALE, &font, "abc");
+
+        let lines = wrap("abcdefghij", max_width);
+
+        assert!(lines.len() > 1);
+        assert_eq!(lines.concat(), "abcdefghij");
+        for line in &lines {
+            assert!(
+                measure(SCALE, &font, line) <= max_width,
+                "{line} is too wide"
+            );
+        }
+    }
+
+    #[test]
+    fn wrap_lines_starts_long_words_on_a_new_line() {
+        let max_width = measure(SCALE, &font(), "abcdef");
+
+        let lines = wrap("ab abcdefghijkl", max_width);
+
+        assert_eq!(lines[0], "ab");
+        assert_eq!(lines[1..].concat(), "abcdefghijkl");
+    }
+
+    #[test]
+    fn wrap_lines_keeps_a_character_wider_than_max_width() {
+        assert_eq!(wrap("WW", 1.0), ["W", "W"]);
+    }
+
+    #[test]
+    fn fit_scale_returns_max_scale_when_it_fits() {
+        let scale = fit_scale("fits", &font(), 10_000.0, 10_000.0, 1.0, 8.0, 64.0);
+
+        assert_eq!(scale, 64.0);
+    }
+
+    #[test]
+    fn fit_scale_shrinks_text_to_fit() {
+        let font = font();
+        let (max_width, max_height) = (200.0, 40.0);
+
+        let scale = fit_scale(
+            "shrink to fit",
+            &font,
+            max_width,
+            max_height,
+            1.0,
+            8.0,
+            64.0,
+        );
+        let block = TextBlock::layout(
+            "shrink to fit",
+            scale,
+            &font,
+            max_width,
+            1.0,
+            TextAlign::Left,
+        );
+
+        assert!(8.0 < scale && scale < 64.0);
+        assert!(block.width <= max_width && block.height <= max_height);
+    }
+
+    #[test]
+    fn fit_scale_stops_at_min_scale() {
+        let scale = fit_scale("does not fit", &font(), 10.0, 1.0, 1.0, 8.0, 64.0);
+
+        assert_eq!(scale, 8.0);
+    }
+}
@@ -1,79 +1,233 @@
 use std::f64::consts::TAU;
 
 use ab_glyph::{Font, GlyphId, OutlinedGlyph, PxScale, Rect, ScaleFont, point};
-use current_previous::CurrentPrevious;
-use image::{Pixel, Rgba, RgbaImage};
+use image::{Pixel, Rgba, RgbaImage, imageops};
 use imageproc::{
     definitions::Clamp,
-    drawing::{Canvas, text_size},
+    drawing::Canvas,
+    geometric_transformations::{Interpolation, rotate},
     pixelops::weighted_sum,
 };
+use serde::{Deserialize, Serialize};
 
-pub fn overlay(
-    mut image: RgbaImage,
-    text: String,
-    text_color: Rgba<u8>,
-    outline_color: Rgba<u8>,
-    scale: f64,
-    thickness: f64,
-    font: impl Font,
-) -> RgbaImage {
-    let image_min = image.width().min(image.height());
-    let margin = image_min as f64 * 0.05;
+use crate::layout::{TextBlock, fit_scale};
 
-    let words = text.split(" ").collect::<Vec<&str>>();
-    let font_scale = scale as f32 * image_min as f32 * 0.1;
-
-    let max_width = image.width() as f64 * 0.75 - 2.0 * margin;
-
-    let thickness = thickness * image_min as f64 * 0.001;
-    let mut line_words = CurrentPrevious::new(Vec::new());
-    let mut y_offset = 0;
-    for word in words {
-        let mut new_line_words = line_words.current().clone();
-        new_line_words.push(word);
-
-        line_words.update(new_line_words);
-
-        let current_line = line_words.current().join(" ");
-        let current_measurement = text_size(font_scale, &font, &current_line);
-
-        if let Some(previous_line_words) = line_words.previous() {
-            let previous_line = previous_line_words.join(" ");
-
-            if (current_measurement.0 as f64) > max_width {
-                draw_text_outline_mut(
-                    &mut image,
-                    text_color,
-                    outline_color,
-                    thickness,
-                    margin as i32,
-                    margin as i32 + y_offset,
-                    font_scale,
-                    &font,
-                    &previous_line,
-                );
-
-                line_words.update(vec![line_words.current().last().unwrap()]);
-                y_offset += font_scale as i32;
-            }
+pub mod animation;
+pub mod font;
+pub mod layout;
+pub mod metadata;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerticalAnchor {
+    #[default]
+    Top,
+    Center,
+    Bottom,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HorizontalAnchor {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// Where the text block sits on the image and how its lines are justified.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LayoutOptions {
+    pub vertical_anchor: VerticalAnchor,
+    pub horizontal_anchor: HorizontalAnchor,
+    pub text_align: TextAlign,
+
+    /// Maximum line width as a fraction of the image width, margins included.
+    pub max_width: f64,
+
+    /// Maximum block height as a fraction of the image height, margins
+    /// included. Only used when fitting text with [`TextScale::Auto`].
+    pub max_height: f64,
+
+    /// Multiplier applied to the font's line height between consecutive lines.
+    pub line_spacing: f64,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        Self {
+            vertical_anchor: VerticalAnchor::default(),
+            horizontal_anchor: HorizontalAnchor::default(),
+            text_align: TextAlign::default(),
+            max_width: 0.75,
+            max_height: 1.0,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+/// Font size relative to a tenth of the image's shorter side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextScale {
+    Fixed(f64),
+
+    /// The largest scale within the bounds at which the wrapped text fits the
+    /// layout's maximum width and height.
+    Auto { min: f64, max: f64 },
+}
+
+impl Default for TextScale {
+    fn default() -> Self {
+        Self::Fixed(1.0)
+    }
+}
+
+/// A single run of styled text drawn onto the image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLayer {
+    pub text: String,
+    pub text_color: Rgba<u8>,
+    pub outline_color: Rgba<u8>,
+    pub scale: TextScale,
+    pub thickness: f64,
+
+    /// Clockwise rotation in degrees about the center of the text block.
+    pub rotation: f64,
+    pub layout: LayoutOptions,
+}
+
+impl Default for TextLayer {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            text_color: Rgba([255, 255, 255, 255]),
+            outline_color: Rgba([0, 0, 0, 255]),
+            scale: TextScale::default(),
+            thickness: 0.0,
+            rotation: 0.0,
+            layout: LayoutOptions::default(),
         }
     }
+}
+
+pub fn overlay(mut image: RgbaImage, layers: &[TextLayer], font: impl Font) -> RgbaImage {
+    for layer in layers {
+        draw_layer_mut(&mut image, layer, &font);
+    }
+
+    image
+}
+
+pub fn draw_layer_mut(image: &mut RgbaImage, layer: &TextLayer, font: &impl Font) {
+    let image_min = image.width().min(image.height());
+    let margin = image_min as f64 * 0.05;
+
+    let scale_unit = image_min as f32 * 0.1;
+
+    let max_width = image.width() as f64 * layer.layout.max_width - 2.0 * margin;
+    let max_height = image.height() as f64 * layer.layout.max_height - 2.0 * margin;
+
+    let font_scale = match layer.scale {
+        TextScale::Fixed(scale) => scale as f32 * scale_unit,
+        TextScale::Auto { min, max } => fit_scale(
+            &layer.text,
+            font,
+            max_width as f32,
+            max_height as f32,
+            layer.layout.line_spacing as f32,
+            min as f32 * scale_unit,
+            max as f32 * scale_unit,
+        ),
+    };
 
-    let current_line = line_words.current().join(" ");
-    draw_text_outline_mut(
-        &mut image,
-        text_color,
-        outline_color,
-        thickness,
-        margin as i32,
-        margin as i32 + y_offset,
+    let thickness = layer.thickness * image_min as f64 * 0.001;
+
+    let block = TextBlock::layout(
+        &layer.text,
         font_scale,
-        &font,
-        &current_line,
+        font,
+        max_width as f32,
+        layer.layout.line_spacing as f32,
+        layer.layout.text_align,
     );
 
-    image
+    let block_width = block.width as f64;
+    let block_height = block.height as f64;
+
+    let block_x = match layer.layout.horizontal_anchor {
+        HorizontalAnchor::Left => margin,
+        HorizontalAnchor::Center => (image.width() as f64 - block_width) / 2.0,
+        HorizontalAnchor::Right => image.width() as f64 - margin - block_width,
+    };
+
+    let block_y = match layer.layout.vertical_anchor {
+        VerticalAnchor::Top => margin,
+        VerticalAnchor::Center => (image.height() as f64 - block_height) / 2.0,
+        VerticalAnchor::Bottom => image.height() as f64 - margin - block_height,
+    };
+
+    // Rotated text is drawn onto a transparent canvas first so the glyphs can be
+    // resampled as a whole before being blended onto the image.
+    let mut rotated_canvas = if layer.rotation % 360.0 != 0.0 {
+        let mut background = if thickness > 0.0 {
+            layer.outline_color
+        } else {
+            layer.text_color
+        };
+        background.0[3] = 0;
+
+        Some(RgbaImage::from_pixel(
+            image.width(),
+            image.height(),
+            background,
+        ))
+    } else {
+        None
+    };
+
+    let canvas = rotated_canvas.as_mut().unwrap_or(&mut *image);
+
+    for line in &block.lines {
+        draw_text_outline_mut(
+            canvas,
+            layer.text_color,
+            layer.outline_color,
+            thickness,
+            (block_x + line.x as f64) as i32,
+            (block_y + line.y as f64) as i32,
+            font_scale,
+            font,
+            &line.text,
+        );
+    }
+
+    if let Some(rotated_canvas) = rotated_canvas {
+        let center = (
+            (block_x + block_width / 2.0) as f32,
+            (block_y + block_height / 2.0) as f32,
+        );
+
+        let rotated = rotate(
+            &rotated_canvas,
+            center,
+            layer.rotation.to_radians() as f32,
+            Interpolation::Bilinear,
+            Rgba([0, 0, 0, 0]),
+        );
+
+        imageops::overlay(image, &rotated, 0, 0);
+    }
 }
 
 pub fn draw_text_mut<C>(
@@ -110,6 +264,7 @@ pub fn draw_text_mut<C>(
     });
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn draw_text_outline_mut<C>(
     canvas: &mut C,
     color: C::Pixel,
@@ -0,0 +1,286 @@
+use image::{
+    ImageError, ImageFormat, ImageResult,
+    error::{DecodingError, ImageFormatHint},
+};
+
+/// Removes metadata from an animated GIF or WebP without re-encoding it, since
+/// animations are stored as uploaded. Other formats are returned unchanged.
+///
+/// GIF comments and application extensions other than the looping ones are
+/// dropped, which covers XMP and color profiles, as are WebP EXIF, XMP and
+/// ICCP chunks. Re-encoded stills lose their color profiles the same way.
+pub fn strip_metadata(bytes: &[u8], format: ImageFormat) -> ImageResult<Vec<u8>> {
+    match format {
+        ImageFormat::Gif => strip_gif(bytes).ok_or_else(|| malformed(format)),
+        ImageFormat::WebP => strip_webp(bytes).ok_or_else(|| malformed(format)),
+        _ => Ok(bytes.to_vec()),
+    }
+}
+
+fn malformed(format: ImageFormat) -> ImageError {
+    ImageError::Decoding(DecodingError::new(
+        ImageFormatHint::Exact(format),
+        "malformed container",
+    ))
+}
+
+const GIF_EXTENSION: u8 = 0x21;
+const GIF_IMAGE: u8 = 0x2C;
+const GIF_TRAILER: u8 = 0x3B;
+const GIF_APPLICATION: u8 = 0xFF;
+const GIF_COMMENT: u8 = 0xFE;
+
+/// Application extensions that control looping rather than carry metadata.
+const GIF_LOOP_APPLICATIONS: [&[u8]; 2] = [b"NETSCAPE2.0", b"ANIMEXTS1.0"];
+
+fn strip_gif(bytes: &[u8]) -> Option<Vec<u8>> {
+    // Header and logical screen descriptor, followed by the global color
+    // table if there is one.
+    let mut position = 13 + color_table_len(*bytes.get(10)?);
+    let mut stripped = bytes.get(..position)?.to_vec();
+
+    loop {
+        let block_start = position;
+
+        match *bytes.get(position)? {
+            GIF_EXTENSION => {
+                let label = *bytes.get(position + 1)?;
+                let first_sub_block = bytes.get(position + 2..)?;
+                position = skip_sub_blocks(bytes, position + 2)?;
+
+                let keep = match label {
+                    GIF_COMMENT => false,
+                    GIF_APPLICATION => GIF_LOOP_APPLICATIONS.iter().any(|application| {
+                        first_sub_block.get(1..1 + application.len()) == Some(*application)
+                    }),
+                    _ => true,
+                };
+
+                if keep {
+                    stripped.extend_from_slice(&bytes[block_start..position]);
+                }
+            }
+            GIF_IMAGE => {
+                // Image descriptor, local color table and LZW minimum code
+                // size, then the image data.
+                position += 10 + color_table_len(*bytes.get(position + 9)?) + 1;
+                position = skip_sub_blocks(bytes, position)?;
+
+                stripped.extend_from_slice(&bytes[block_start..position]);
+            }
+            GIF_TRAILER => {
+                stripped.push(GIF_TRAILER);
+
+                return Some(stripped);
+            }
+            _ => return None,
+        }
+    }
+}
+
+fn color_table_len(packed: u8) -> usize {
+    if packed & 0x80 != 0 {
+        3 << ((packed & 0x07) + 1)
+    } else {
+        0
+    }
+}
+
+/// Returns the position after the sub-blocks starting at `position`,
+/// including their terminator.
+fn skip_sub_blocks(bytes: &[u8], mut position: usize) -> Option<usize> {
+    loop {
+        let len = *bytes.get(position)? as usize;
+        position += 1 + len;
+
+        if len == 0 {
+            return (position <= bytes.len()).then_some(position);
+        }
+    }
+}
+
+pub(crate) const WEBP_ICC_FLAG: u8 = 0x20;
+pub(crate) const WEBP_EXIF_FLAG: u8 = 0x08;
+pub(crate) const WEBP_XMP_FLAG: u8 = 0x04;
+
+fn strip_webp(bytes: &[u8]) -> Option<Vec<u8>> {
+    if bytes.get(..4)? != b"RIFF" || bytes.get(8..12)? != b"WEBP" {
+        return None;
+    }
+
+    let mut stripped = b"RIFF\0\0\0\0WEBP".to_vec();
+    let mut position = 12;
+
+    while position < bytes.len() {
+        let header = bytes.get(position..position + 8)?;
+        let fourcc = &header[..4];
+        let len = u32::from_le_bytes(header[4..].try_into().ok()?);
+        // Checked since a huge length would wrap around on 32-bit targets.
+        let data_end = (position + 8).checked_add(usize::try_from(len).ok()?)?;
+        // Chunks are padded to an even length, though encoders sometimes leave
+        // the padding off the last one.
+        let end = data_end.checked_next_multiple_of(2)?;
+        let chunk = bytes
+            .get(position..end)
+            .or_else(|| bytes.get(position..data_end))?;
+
+        match fourcc {
+            b"EXIF" | b"XMP " | b"ICCP" => {}
+            b"VP8X" => {
+                let flags_position = stripped.len() + 8;
+                stripped.extend_from_slice(chunk);
+                *stripped.get_mut(flags_position)? &=
+                    !(WEBP_ICC_FLAG | WEBP_EXIF_FLAG | WEBP_XMP_FLAG);
+            }
+            _ => stripped.extend_from_slice(chunk),
+        }
+
+        position = end;
+    }
+
+    let riff_len = u32::try_from(stripped.len() - 8).ok()?;
+    stripped[4..8].copy_from_slice(&riff_len.to_le_bytes());
+
+    Some(stripped)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{AnimationDecoder, Delay, Frame, Rgba, RgbaImage, codecs::gif::GifDecoder};
+
+    use super::*;
+    use crate::{
+        animation::encode_gif,
+        test_support::{GIF_METADATA_MARKERS, contains, webp_with_metadata, with_gif_metadata},
+    };
+
+    fn frame_image(index: u8) -> RgbaImage {
+        RgbaImage::from_fn(4, 2, |x, y| {
+            Rgba([index * 50, x as u8 * 60, y as u8 * 120, 255])
+        })
+    }
+
+    /// A two frame GIF with a comment, XMP and a color profile after its
+    /// frames.
+    fn gif_with_metadata() -> Vec<u8> {
+        let frames = (0..2)
+            .map(|index| {
+                Frame::from_parts(frame_image(index), 0, 0, Delay::from_numer_denom_ms(100, 1))
+            })
+            .collect();
+
+        with_gif_metadata(encode_gif(frames).unwrap())
+    }
+
+    fn gif_frames(bytes: &[u8]) -> Vec<RgbaImage> {
+        GifDecoder::new(Cursor::new(bytes))
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap()
+            .into_iter()
+            .map(Frame::into_buffer)
+            .collect()
+    }
+
+    #[test]
+    fn gif_metadata_is_stripped() {
+        let bytes = gif_with_metadata();
+        let stripped = strip_metadata(&bytes, ImageFormat::Gif).unwrap();
+
+        for needle in GIF_METADATA_MARKERS {
+            assert!(contains(&bytes, needle));
+            assert!(!contains(&stripped, needle));
+        }
+
+        assert!(contains(&stripped, b"NETSCAPE2.0"));
+        assert_eq!(gif_frames(&stripped), gif_frames(&bytes));
+    }
+
+    #[test]
+    fn webp_metadata_is_stripped() {
+        let bytes = webp_with_metadata(&frame_image(0));
+        let stripped = strip_metadata(&bytes, ImageFormat::WebP).unwrap();
+
+        for fourcc in [b"ICCP", b"EXIF", b"XMP "] {
+            assert!(contains(&bytes, fourcc));
+            assert!(!contains(&stripped, fourcc));
+        }
+
+        assert_eq!(
+            stripped[20] & (WEBP_ICC_FLAG | WEBP_EXIF_FLAG | WEBP_XMP_FLAG),
+            0
+        );
+        assert_eq!(
+            u32::from_le_bytes(stripped[4..8].try_into().unwrap()) as usize,
+            stripped.len() - 8
+        );
+        assert_eq!(
+            image::load_from_memory(&stripped).unwrap().into_rgba8(),
+            frame_image(0)
+        );
+    }
+
+    #[test]
+    fn other_formats_are_unchanged() {
+        assert_eq!(
+            strip_metadata(b"not a gif", ImageFormat::Png).unwrap(),
+            b"not a gif"
+        );
+    }
+
+    #[test]
+    fn truncated_gifs_are_malformed() {
+        let bytes = gif_with_metadata();
+
+        for len in 0..bytes.len() {
+            assert_eq!(strip_gif(&bytes[..len]), None, "{len} bytes");
+        }
+    }
+
+    #[test]
+    fn malformed_gifs_are_rejected() {
+        let bytes = gif_with_metadata();
+
+        // A global color table far larger than the file.
+        let mut huge_color_table = bytes.clone();
+        huge_color_table[10] |= 0x87;
+        assert_eq!(strip_gif(&huge_color_table), None);
+
+        // A comment whose sub-block runs past the end.
+        let mut overlong_sub_block = bytes[..bytes.len() - 1].to_vec();
+        overlong_sub_block.extend_from_slice(b"\x21\xFE\xFFshort");
+        assert_eq!(strip_gif(&overlong_sub_block), None);
+
+        // A block that is neither an extension, an image nor the trailer.
+        let mut unknown_block = bytes[..bytes.len() - 1].to_vec();
+        unknown_block.extend_from_slice(b"\x00\x3B");
+        assert_eq!(strip_gif(&unknown_block), None);
+    }
+
+    #[test]
+    fn truncated_webps_are_stripped_or_malformed() {
+        let bytes = webp_with_metadata(&frame_image(0));
+
+        for len in 0..bytes.len() {
+            if let Some(stripped) = strip_webp(&bytes[..len]) {
+                assert!(stripped.len() <= len, "{len} bytes");
+            }
+        }
+
+        assert_eq!(strip_webp(&bytes[..12 + 7]), None);
+    }
+
+    #[test]
+    fn malformed_webps_are_rejected() {
+        let mut not_riff = webp_with_metadata(&frame_image(0));
+        not_riff[..4].copy_from_slice(b"RIFX");
+        assert_eq!(strip_webp(&not_riff), None);
+
+        let mut overlong_chunk = webp_with_metadata(&frame_image(0));
+        overlong_chunk[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(strip_webp(&overlong_chunk), None);
+    }
+}
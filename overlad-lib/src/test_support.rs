@@ -0,0 +1,71 @@
+//! Images carrying metadata, for the tests here and in the backend, which
+//! enables the `test-support` feature for its tests.
+
+use std::io::Cursor;
+
+use image::{ImageFormat, RgbaImage};
+
+use crate::metadata::{WEBP_EXIF_FLAG, WEBP_ICC_FLAG, WEBP_XMP_FLAG};
+
+/// What [`with_gif_metadata`] adds: a comment, XMP and a color profile.
+pub const GIF_METADATA_MARKERS: [&[u8]; 3] = [b"comment", b"XMP DataXMP", b"ICCRGBG1012"];
+
+/// The alpha flag of the WebP extended header.
+const WEBP_ALPHA_FLAG: u8 = 0x10;
+
+/// Adds a comment, XMP and a color profile after the frames of a GIF.
+pub fn with_gif_metadata(mut gif: Vec<u8>) -> Vec<u8> {
+    let trailer = gif.pop();
+    assert_eq!(trailer, Some(0x3B), "not a whole GIF");
+
+    gif.extend_from_slice(b"\x21\xFE\x07comment\x00");
+    gif.extend_from_slice(b"\x21\xFF\x0BXMP DataXMP\x0A<x:xmpmeta\x00");
+    gif.extend_from_slice(b"\x21\xFF\x0BICCRGBG1012\x04icc!\x00");
+    gif.extend(trailer);
+
+    gif
+}
+
+/// A RIFF chunk, padded to an even length.
+pub fn riff_chunk(fourcc: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = fourcc.to_vec();
+    chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    chunk.extend_from_slice(data);
+
+    if data.len() % 2 == 1 {
+        chunk.push(0);
+    }
+
+    chunk
+}
+
+/// `image` as a lossless WebP with a color profile, EXIF and XMP, announced
+/// in its extended header.
+pub fn webp_with_metadata(image: &RgbaImage) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut encoded), ImageFormat::WebP)
+        .unwrap();
+
+    let flags = WEBP_ICC_FLAG | WEBP_ALPHA_FLAG | WEBP_EXIF_FLAG | WEBP_XMP_FLAG;
+    let mut extended_header = vec![flags, 0, 0, 0];
+    extended_header.extend_from_slice(&(image.width() - 1).to_le_bytes()[..3]);
+    extended_header.extend_from_slice(&(image.height() - 1).to_le_bytes()[..3]);
+
+    let mut bytes = b"RIFF\0\0\0\0WEBP".to_vec();
+    bytes.extend(riff_chunk(b"VP8X", &extended_header));
+    bytes.extend(riff_chunk(b"ICCP", b"icc!"));
+    bytes.extend_from_slice(&encoded[12..]);
+    bytes.extend(riff_chunk(b"EXIF", b"MM\x00\x2A\x00\x00\x00\x08\x00\x00"));
+    // An odd length, so the chunk is padded.
+    bytes.extend(riff_chunk(b"XMP ", b"http://ns.adobe.com/xap/1.0/?"));
+
+    let riff_len = (bytes.len() - 8) as u32;
+    bytes[4..8].copy_from_slice(&riff_len.to_le_bytes());
+
+    bytes
+}
+
+pub fn contains(bytes: &[u8], needle: &[u8]) -> bool {
+    bytes.windows(needle.len()).any(|window| window == needle)
+}